@@ -0,0 +1,387 @@
+//! Device health alerts: low batteries, devices offline for too long, mounting errors
+//! and firmware updates, raised once and resolved when the condition clears.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use strum::Display;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    data::{new_enum, unix_seconds, Device, DeviceBatteryState, DeviceId, DeviceMountingState},
+    Client, Error,
+};
+
+new_enum![
+    #[serde(tag = "kind", rename_all_fields = "camelCase")]
+    Alert {
+        BatteryLow { device: DeviceId },
+        Offline { device: DeviceId, minutes: u64 },
+        MountingError { device: DeviceId, state: DeviceMountingState },
+        FirmwareChanged { device: DeviceId, from: String, to: String }
+    }
+];
+
+impl Alert {
+    pub fn device(&self) -> &DeviceId {
+        match self {
+            Alert::BatteryLow { device }
+            | Alert::Offline { device, .. }
+            | Alert::MountingError { device, .. }
+            | Alert::FirmwareChanged { device, .. } => device,
+        }
+    }
+}
+
+new_enum![
+    #[serde(tag = "event")]
+    AlertEvent {
+        Raised { alert: Alert },
+        Resolved { alert: Alert }
+    }
+];
+
+/// Receives alert events, e.g. to forward them to an on-call system
+pub trait AlertSink {
+    fn send(&self, event: &AlertEvent) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+impl AlertSink for UnboundedSender<AlertEvent> {
+    async fn send(&self, event: &AlertEvent) -> Result<(), Error> {
+        // A dropped receiver just means nobody listens anymore
+        let _ = UnboundedSender::send(self, event.clone());
+
+        Ok(())
+    }
+}
+
+pub struct AlertConfiguration {
+    /// How long a device must stay disconnected before being reported
+    pub offline_after: Duration,
+    pub interval: Duration,
+}
+
+impl Default for AlertConfiguration {
+    fn default() -> Self {
+        Self {
+            offline_after: Duration::from_secs(30 * 60),
+            interval: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+pub struct AlertMonitor<S> {
+    sink: S,
+    configuration: AlertConfiguration,
+    active: HashMap<(DeviceId, String), Alert>,
+    /// First time devices were seen offline, for connection timestamps that can't be read
+    offline_since: HashMap<DeviceId, SystemTime>,
+    firmware: HashMap<DeviceId, String>,
+}
+
+impl<S: AlertSink> AlertMonitor<S> {
+    pub fn new(sink: S, configuration: AlertConfiguration) -> Self {
+        Self {
+            sink,
+            configuration,
+            active: HashMap::new(),
+            offline_since: HashMap::new(),
+            firmware: HashMap::new(),
+        }
+    }
+
+    /// Alerts raised and not resolved yet
+    pub fn active(&self) -> impl Iterator<Item = &Alert> {
+        self.active.values()
+    }
+
+    /// Compares the devices with the previous evaluation and returns what changed
+    pub fn evaluate(&mut self, devices: &[Device], now: SystemTime) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        let mut current = HashMap::new();
+
+        for device in devices {
+            let serial = &device.serial_no;
+            let mut alerts = Vec::new();
+
+            if device.battery_state == Some(DeviceBatteryState::Low) {
+                alerts.push(Alert::BatteryLow {
+                    device: serial.clone(),
+                });
+            }
+
+            if device.connection_state.value {
+                self.offline_since.remove(serial);
+            } else {
+                // The connection timestamp tells since when the device is disconnected,
+                // even for devices that were already offline when the monitor started
+                let since = match unix_seconds(&device.connection_state.timestamp) {
+                    Some(seconds) => {
+                        UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).unwrap_or(0))
+                    }
+                    None => *self.offline_since.entry(serial.clone()).or_insert(now),
+                };
+                let offline = now.duration_since(since).unwrap_or_default();
+
+                if offline >= self.configuration.offline_after {
+                    alerts.push(Alert::Offline {
+                        device: serial.clone(),
+                        minutes: offline.as_secs() / 60,
+                    });
+                }
+            }
+
+            if let Some(state) = device
+                .mounting_state_with_error
+                .as_ref()
+                .filter(|state| **state == DeviceMountingState::MountingError)
+            {
+                alerts.push(Alert::MountingError {
+                    device: serial.clone(),
+                    state: state.clone(),
+                });
+            }
+
+            // Firmware updates are only notified, there is nothing to resolve
+            match self
+                .firmware
+                .insert(serial.clone(), device.current_fw_version.clone())
+            {
+                Some(previous) if previous != device.current_fw_version => {
+                    events.push(AlertEvent::Raised {
+                        alert: Alert::FirmwareChanged {
+                            device: serial.clone(),
+                            from: previous,
+                            to: device.current_fw_version.clone(),
+                        },
+                    })
+                }
+                _ => {}
+            }
+
+            // Alerts are de-duplicated by device and kind, not by their details
+            for alert in alerts {
+                current.insert((serial.clone(), alert.to_string()), alert);
+            }
+        }
+
+        let resolved: Vec<_> = self
+            .active
+            .keys()
+            .filter(|key| !current.contains_key(*key))
+            .cloned()
+            .collect();
+
+        for key in resolved {
+            if let Some(alert) = self.active.remove(&key) {
+                events.push(AlertEvent::Resolved { alert });
+            }
+        }
+
+        for (key, alert) in current {
+            if let Entry::Vacant(entry) = self.active.entry(key) {
+                events.push(AlertEvent::Raised {
+                    alert: alert.clone(),
+                });
+                entry.insert(alert);
+            }
+        }
+
+        events
+    }
+
+    /// Fetches the devices once and forwards the resulting events to the sink.
+    /// Events the sink rejects are sent again on the next check.
+    pub async fn check(&mut self, client: &Client) -> Result<Vec<AlertEvent>, Error> {
+        let devices = client.get_devices().await?;
+
+        self.notify(&devices, SystemTime::now()).await
+    }
+
+    async fn notify(
+        &mut self,
+        devices: &[Device],
+        now: SystemTime,
+    ) -> Result<Vec<AlertEvent>, Error> {
+        let events = self.evaluate(devices, now);
+
+        for (index, event) in events.iter().enumerate() {
+            if let Err(error) = self.sink.send(event).await {
+                for event in &events[index..] {
+                    self.revert(event);
+                }
+
+                return Err(error);
+            }
+        }
+
+        Ok(events)
+    }
+
+    // Forgets an undelivered event, so the next evaluation produces it again
+    fn revert(&mut self, event: &AlertEvent) {
+        match event {
+            AlertEvent::Raised {
+                alert: Alert::FirmwareChanged { device, from, .. },
+            } => {
+                self.firmware.insert(device.clone(), from.clone());
+            }
+            AlertEvent::Raised { alert } => {
+                self.active
+                    .remove(&(alert.device().clone(), alert.to_string()));
+            }
+            AlertEvent::Resolved { alert } => {
+                self.active
+                    .insert((alert.device().clone(), alert.to_string()), alert.clone());
+            }
+        }
+    }
+
+    /// Checks the devices on every interval. Failures are logged and the check
+    /// is retried on the next interval, so this never returns.
+    pub async fn run(&mut self, client: &Client) {
+        let mut interval = tokio::time::interval(self.configuration.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = self.check(client).await {
+                log::warn!("Failed to check device alerts: {}", error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+
+    // 2024-03-01T10:15:30Z
+    const DISCONNECTED_AT: u64 = 1_709_288_130;
+
+    fn device(online: bool, mounting_state: &str) -> Device {
+        serde_json::from_value(json!({
+            "deviceType": "VA02",
+            "serialNo": "VA123",
+            "shortSerialNo": "VA123",
+            "currentFwVersion": "57.2",
+            "characteristics": { "capabilities": [] },
+            "mountingStateWithError": mounting_state,
+            "batteryState": "NORMAL",
+            "connectionState": { "value": online, "timestamp": "2024-03-01T10:15:30.000Z" },
+        }))
+        .unwrap()
+    }
+
+    fn monitor() -> AlertMonitor<UnboundedSender<AlertEvent>> {
+        let (sender, _) = tokio::sync::mpsc::unbounded_channel();
+
+        AlertMonitor::new(sender, AlertConfiguration::default())
+    }
+
+    /// Rejects every event while failing is set
+    struct FlakySink(AtomicBool);
+
+    impl AlertSink for FlakySink {
+        async fn send(&self, _: &AlertEvent) -> Result<(), Error> {
+            match self.0.load(Ordering::SeqCst) {
+                true => Err(Error::InvalidInput("Sink unavailable".to_string())),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn undelivered_events_are_sent_again() {
+        let mut monitor = AlertMonitor::new(
+            FlakySink(AtomicBool::new(true)),
+            AlertConfiguration::default(),
+        );
+        let now = UNIX_EPOCH + Duration::from_secs(DISCONNECTED_AT);
+
+        assert!(monitor
+            .notify(&[device(true, "MOUNTING_ERROR")], now)
+            .await
+            .is_err());
+        assert_eq!(monitor.active().count(), 0);
+
+        monitor.sink.0.store(false, Ordering::SeqCst);
+        let events = monitor
+            .notify(&[device(true, "MOUNTING_ERROR")], now)
+            .await
+            .unwrap();
+        assert!(matches!(&events[..], [AlertEvent::Raised { .. }]));
+
+        monitor.sink.0.store(true, Ordering::SeqCst);
+        assert!(monitor
+            .notify(&[device(true, "CALIBRATED")], now)
+            .await
+            .is_err());
+        assert_eq!(monitor.active().count(), 1);
+
+        monitor.sink.0.store(false, Ordering::SeqCst);
+        let events = monitor
+            .notify(&[device(true, "CALIBRATED")], now)
+            .await
+            .unwrap();
+        assert!(matches!(&events[..], [AlertEvent::Resolved { .. }]));
+        assert_eq!(monitor.active().count(), 0);
+    }
+
+    #[test]
+    fn offline_time_starts_at_the_connection_timestamp() {
+        let mut monitor = monitor();
+        let now = UNIX_EPOCH + Duration::from_secs(DISCONNECTED_AT + 2 * 60 * 60);
+        let events = monitor.evaluate(&[device(false, "CALIBRATED")], now);
+
+        assert_eq!(
+            events,
+            vec![AlertEvent::Raised {
+                alert: Alert::Offline {
+                    device: "VA123".to_string(),
+                    minutes: 120,
+                },
+            }]
+        );
+
+        let events = monitor.evaluate(&[device(true, "CALIBRATED")], now);
+        assert!(matches!(&events[..], [AlertEvent::Resolved { .. }]));
+    }
+
+    #[test]
+    fn recently_disconnected_devices_are_not_reported() {
+        let mut monitor = monitor();
+        let now = UNIX_EPOCH + Duration::from_secs(DISCONNECTED_AT + 60);
+
+        assert!(monitor
+            .evaluate(&[device(false, "CALIBRATED")], now)
+            .is_empty());
+    }
+
+    #[test]
+    fn only_mounting_errors_are_reported() {
+        let mut monitor = monitor();
+        let now = UNIX_EPOCH + Duration::from_secs(DISCONNECTED_AT);
+
+        assert!(monitor
+            .evaluate(&[device(true, "CALIBRATING")], now)
+            .is_empty());
+        assert!(monitor
+            .evaluate(&[device(true, "SOMETHING_NEW")], now)
+            .is_empty());
+        assert_eq!(
+            monitor
+                .evaluate(&[device(true, "MOUNTING_ERROR")], now)
+                .len(),
+            1
+        );
+    }
+}
@@ -3,3 +3,7 @@ pub static CLIENT_SECRET: &str = "wZaRN7rpjn3FoNyF5IFuxg9uMzYJcvOoQ8QWiIqS3hfk6g
 pub static AUTH_SCOPE: &str = "home.user";
 pub static AUTH_URL: &str = "https://auth.tado.com/oauth/token";
 pub static API_URL: &str = "https://my.tado.com/api/v2/";
+pub static ENERGY_BOB_URL: &str = "https://energy-bob.tado.com/";
+pub static MINDER_URL: &str = "https://minder.tado.com/v1/";
+pub static BOILER_URL: &str = "https://ivar.tado.com/";
+pub static HOPS_URL: &str = "https://hops.tado.com/";
@@ -1,7 +1,11 @@
+use std::{ops::RangeInclusive, time::Duration};
+
 use serde::{Deserialize, Serialize};
 
 use strum::Display;
 
+use crate::Error;
+
 macro_rules! new_type {
     ($name:ident { $($(#[$type_attr:meta])* $field_name:ident : $field_type:ty),* $(,)? }) => {
         #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,25 +25,30 @@ macro_rules! new_type {
 }
 
 macro_rules! new_enum {
-    ($(#[$attr:meta])* $enum_name:ident { $($variant:ident $( { $($field:ident : $field_type:ty),* } )? ),* $(,)? }) => {
-        #[derive(Debug, Clone, Serialize, Deserialize, Display)]
+    ($(#[$attr:meta])* $enum_name:ident { $($(#[$variant_attr:meta])* $variant:ident $( { $($field:ident : $field_type:ty),* } )? ),* $(,)? }) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
         #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
         #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
         $(#[$attr])*
         pub enum $enum_name {
             $(
-                $variant $( { $($field : $field_type),* } )? ,
+                $(#[$variant_attr])* $variant $( { $($field : $field_type),* } )? ,
             )*
         }
     };
 }
 
+pub(crate) use new_enum;
+pub(crate) use new_type;
+
 pub type ZoneId = u32;
 
 pub type HomeId = u32;
 
 pub type DeviceId = String;
 
+pub type MobileDeviceId = u32;
+
 #[cfg(feature = "chrono")]
 pub type Date = chrono::DateTime<chrono::Utc>;
 
@@ -52,6 +61,45 @@ pub type Timezone = chrono_tz::Tz;
 #[cfg(not(feature = "chrono"))]
 pub type Timezone = String;
 
+// Seconds since the Unix epoch of a timestamp from the API
+#[cfg(feature = "chrono")]
+pub(crate) fn unix_seconds(date: &Date) -> Option<i64> {
+    Some(date.timestamp())
+}
+
+// Seconds since the Unix epoch of a timestamp from the API, which are
+// RFC 3339 dates in UTC such as 2024-03-01T10:15:30.000Z
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn unix_seconds(date: &Date) -> Option<i64> {
+    let (date, time) = date.strip_suffix('Z')?.split_once('T')?;
+    let date: Vec<i64> = date
+        .splitn(3, '-')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let time: Vec<i64> = time
+        .get(..8)?
+        .splitn(3, ':')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    let (&[year, month, day], &[hours, minutes, seconds]) = (&date[..], &time[..]) else {
+        return None;
+    };
+
+    // Days since the epoch of a proleptic Gregorian date, see
+    // https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
+
 new_type![Support {
     enabled: bool,
     supported: bool,
@@ -70,17 +118,176 @@ new_enum![ZoneType {
     AirConditioning
 }];
 
-new_type![Temperature {
+impl ZoneType {
+    /// Temperatures, in celsius, accepted by tado for this kind of zone
+    pub fn temperature_range(&self) -> RangeInclusive<f32> {
+        match self {
+            ZoneType::Heating => 5.0..=25.0,
+            ZoneType::HotWater => 30.0..=65.0,
+            ZoneType::AirConditioning => 16.0..=30.0,
+        }
+    }
+
+    pub fn validate_temperature(&self, temperature: &Temperature) -> Result<(), Error> {
+        let range = self.temperature_range();
+
+        if range.contains(&temperature.celsius) {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "{}°C is outside of the {}..={}°C range allowed for {} zones",
+                temperature.celsius,
+                range.start(),
+                range.end(),
+                self
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", from = "TemperatureValue")]
+pub struct Temperature {
+    pub celsius: f32,
+    pub fahrenheit: f32,
+}
+
+// Temperatures as read, where fahrenheit can be left out of hand written documents
+#[derive(Deserialize)]
+struct TemperatureValue {
     celsius: f32,
-    fahrenheit: f32,
+    fahrenheit: Option<f32>,
+}
+
+impl From<TemperatureValue> for Temperature {
+    fn from(value: TemperatureValue) -> Self {
+        match value.fahrenheit {
+            Some(fahrenheit) => Self {
+                celsius: value.celsius,
+                fahrenheit,
+            },
+            None => Self::from_celsius(value.celsius),
+        }
+    }
+}
+
+impl Temperature {
+    pub fn from_celsius(celsius: f32) -> Self {
+        Self {
+            celsius,
+            fahrenheit: celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+}
+
+new_enum![Power { On, Off }];
+
+new_enum![AcMode {
+    Cool,
+    Heat,
+    Dry,
+    Fan,
+    Auto,
+}];
+
+new_enum![AcFanLevel {
+    Silent,
+    Level1,
+    Level2,
+    Level3,
+    Level4,
+    Level5,
+    Auto,
+}];
+
+// Used by older air conditioning devices instead of fan levels
+new_enum![AcFanSpeed {
+    Auto,
+    High,
+    Middle,
+    Low,
+}];
+
+new_enum![AcVerticalSwing {
+    Off,
+    On,
+    Up,
+    MidUp,
+    Mid,
+    MidDown,
+    Down,
+    Auto,
 }];
 
+new_enum![AcHorizontalSwing {
+    Off,
+    On,
+    Left,
+    MidLeft,
+    Mid,
+    MidRight,
+    Right,
+    Auto,
+}];
+
+new_type![ZoneSetting {
+    r#type: ZoneType,
+    power: Power,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<Temperature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<AcMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fan_level: Option<AcFanLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fan_speed: Option<AcFanSpeed>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vertical_swing: Option<AcVerticalSwing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    horizontal_swing: Option<AcHorizontalSwing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    swing: Option<Power>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    light: Option<Power>,
+}];
+
+impl ZoneSetting {
+    pub fn new(r#type: ZoneType, power: Power, temperature: Option<Temperature>) -> Self {
+        Self {
+            r#type,
+            power,
+            temperature,
+            mode: None,
+            fan_level: None,
+            fan_speed: None,
+            vertical_swing: None,
+            horizontal_swing: None,
+            swing: None,
+            light: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        match &self.temperature {
+            Some(temperature) => self.r#type.validate_temperature(temperature),
+            None => Ok(()),
+        }
+    }
+}
+
 new_enum![TemperatureUnit {
     Celsius,
     Fahrenheit,
 }];
 
-new_enum![DeviceMountingState { Calibrated }];
+new_enum![DeviceMountingState {
+    Calibrated,
+    Calibrating,
+    Unmounted,
+    MountingError,
+    #[serde(other)]
+    Unknown,
+}];
 
 new_enum![DeviceBatteryState { Normal, Low }];
 
@@ -124,6 +331,48 @@ new_type! [Device {
     is_driver_configured: Option<bool>,
 }];
 
+impl Device {
+    pub fn can_measure_temperature(&self) -> bool {
+        self.characteristics
+            .capabilities
+            .contains(&DeviceCharacteristicsCapabilities::InsideTemperatureMeasurement)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum InstallationState {
+    Completed,
+    Installing,
+    /// States not known by the library, kept as sent by the API
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl std::fmt::Display for InstallationState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallationState::Completed => write!(f, "COMPLETED"),
+            InstallationState::Installing => write!(f, "INSTALLING"),
+            InstallationState::Other(state) => write!(f, "{}", state),
+        }
+    }
+}
+
+new_type![Installation {
+    id: u32,
+    r#type: String,
+    revision: u32,
+    state: InstallationState,
+    devices: Vec<Device>,
+}];
+
+impl Installation {
+    pub fn is_completed(&self) -> bool {
+        self.state == InstallationState::Completed
+    }
+}
+
 new_type![Invitation {
     token: String,
     email: String,
@@ -168,13 +417,24 @@ new_type![MobileDeviceMetadata {
 }];
 
 new_type![MobileDevice {
-    id: u32,
+    id: MobileDeviceId,
     name: String,
     settings: MobileDeviceSettings,
     location: Option<MobileDeviceLocation>,
     device_metadata: MobileDeviceMetadata,
 }];
 
+impl MobileDevice {
+    /// Whether the device location is currently taken into account to switch the home between HOME and AWAY
+    pub fn decides_presence(&self) -> bool {
+        self.settings.geo_tracking_enabled
+            && self
+                .location
+                .as_ref()
+                .is_some_and(|location| !location.stale)
+    }
+}
+
 new_type![HomeState {
     presence: StatePresence,
     presence_locked: bool,
@@ -210,6 +470,8 @@ new_enum![HomeFeatures {
     SmartScheduleAsWebview,
 }];
 
+new_enum![HomeGeneration { PreLineX, LineX }];
+
 new_type![BasicHome {
     id: HomeId,
     name: String,
@@ -244,6 +506,24 @@ new_type![Home {
     is_balance_hp_eligible: bool,
     is_energy_iq_eligible: bool,
     is_heat_source_installed: bool,
+    generation: Option<HomeGeneration>,
+}];
+
+impl Home {
+    /// Away radius, in meters, accepted by tado
+    pub const AWAY_RADIUS_RANGE: RangeInclusive<f32> = 100.0..=10_000.0;
+
+    /// Whether the home is made of tado X devices, which are only served by the [`crate::x`] API
+    pub fn is_tado_x(&self) -> bool {
+        self.generation == Some(HomeGeneration::LineX)
+    }
+}
+
+new_type![HomeDetails {
+    name: String,
+    address: HomeAddress,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geolocation: Option<Geolocation>,
 }];
 
 new_type![User {
@@ -276,10 +556,310 @@ new_type![Zone {
     open_window_detection: ZoneOpenWindowDetection,
 }];
 
+new_enum![
+    #[serde(tag = "type", rename_all_fields = "camelCase")]
+    OverlayTerminationCondition {
+        Manual,
+        TadoMode,
+        Timer { duration_in_seconds: u32 }
+    }
+];
+
+/// Seconds of a timer, which must last at least a second and fit the API
+pub(crate) fn timer_seconds(duration: Duration) -> Result<u32, Error> {
+    match u32::try_from(duration.as_secs()) {
+        Ok(0) | Err(_) => Err(Error::InvalidInput(format!(
+            "Timer of {}s must be between 1s and {}s",
+            duration.as_secs(),
+            u32::MAX
+        ))),
+        Ok(seconds) => Ok(seconds),
+    }
+}
+
+impl OverlayTerminationCondition {
+    pub fn timer(duration: Duration) -> Result<Self, Error> {
+        Ok(OverlayTerminationCondition::Timer {
+            duration_in_seconds: timer_seconds(duration)?,
+        })
+    }
+}
+
+new_type![DefaultOverlay {
+    termination_condition: OverlayTerminationCondition,
+}];
+
+new_type![Overlay {
+    setting: ZoneSetting,
+    termination: OverlayTerminationCondition,
+}];
+
+new_type![TemperatureRange {
+    min: f32,
+    max: f32,
+    step: f32,
+}];
+
+new_type![CapabilityTemperatures {
+    celsius: TemperatureRange,
+    fahrenheit: Option<TemperatureRange>,
+}];
+
+new_type![AcModeCapabilities {
+    temperatures: Option<CapabilityTemperatures>,
+    fan_level: Option<Vec<AcFanLevel>>,
+    fan_speeds: Option<Vec<AcFanSpeed>>,
+    vertical_swing: Option<Vec<AcVerticalSwing>>,
+    horizontal_swing: Option<Vec<AcHorizontalSwing>>,
+    swings: Option<Vec<Power>>,
+    light: Option<Vec<Power>>,
+}];
+
+new_type![ZoneCapabilities {
+    r#type: ZoneType,
+    temperatures: Option<CapabilityTemperatures>,
+    can_set_temperature: Option<bool>,
+    #[serde(rename = "COOL")]
+    cool: Option<AcModeCapabilities>,
+    #[serde(rename = "HEAT")]
+    heat: Option<AcModeCapabilities>,
+    #[serde(rename = "DRY")]
+    dry: Option<AcModeCapabilities>,
+    #[serde(rename = "FAN")]
+    fan: Option<AcModeCapabilities>,
+    #[serde(rename = "AUTO")]
+    auto: Option<AcModeCapabilities>,
+}];
+
+fn validate_supported<T: PartialEq + std::fmt::Display>(
+    name: &str,
+    value: &Option<T>,
+    supported: &Option<Vec<T>>,
+) -> Result<(), Error> {
+    match (value, supported) {
+        (Some(value), Some(supported)) if supported.contains(value) => Ok(()),
+        (Some(value), _) => Err(Error::InvalidInput(format!(
+            "{} {} is not supported by the zone",
+            name, value
+        ))),
+        (None, _) => Ok(()),
+    }
+}
+
+fn validate_range(temperature: &Temperature, range: &TemperatureRange) -> Result<(), Error> {
+    if (range.min..=range.max).contains(&temperature.celsius) {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "{}°C is outside of the {}..={}°C range supported by the zone",
+            temperature.celsius, range.min, range.max
+        )))
+    }
+}
+
+impl ZoneCapabilities {
+    pub fn mode(&self, mode: &AcMode) -> Option<&AcModeCapabilities> {
+        match mode {
+            AcMode::Cool => self.cool.as_ref(),
+            AcMode::Heat => self.heat.as_ref(),
+            AcMode::Dry => self.dry.as_ref(),
+            AcMode::Fan => self.fan.as_ref(),
+            AcMode::Auto => self.auto.as_ref(),
+        }
+    }
+
+    pub fn supported_modes(&self) -> Vec<AcMode> {
+        [
+            AcMode::Cool,
+            AcMode::Heat,
+            AcMode::Dry,
+            AcMode::Fan,
+            AcMode::Auto,
+        ]
+        .into_iter()
+        .filter(|mode| self.mode(mode).is_some())
+        .collect()
+    }
+
+    /// Checks that the setting can be applied to a zone with these capabilities
+    pub fn validate(&self, setting: &ZoneSetting) -> Result<(), Error> {
+        if setting.r#type != self.r#type {
+            return Err(Error::InvalidInput(format!(
+                "{} setting cannot be used for {} zones",
+                setting.r#type, self.r#type
+            )));
+        }
+
+        if setting.power == Power::Off {
+            return Ok(());
+        }
+
+        match self.r#type {
+            ZoneType::AirConditioning => {
+                let mode = setting.mode.as_ref().ok_or_else(|| {
+                    Error::InvalidInput("Air conditioning settings need a mode".to_string())
+                })?;
+
+                let capabilities = self.mode(mode).ok_or_else(|| {
+                    Error::InvalidInput(format!("Mode {} is not supported by the zone", mode))
+                })?;
+
+                match (&setting.temperature, &capabilities.temperatures) {
+                    (Some(temperature), Some(temperatures)) => {
+                        validate_range(temperature, &temperatures.celsius)?
+                    }
+                    (Some(_), None) => {
+                        return Err(Error::InvalidInput(format!(
+                            "Mode {} does not accept a temperature",
+                            mode
+                        )))
+                    }
+                    _ => {}
+                }
+
+                validate_supported("Fan level", &setting.fan_level, &capabilities.fan_level)?;
+                validate_supported("Fan speed", &setting.fan_speed, &capabilities.fan_speeds)?;
+                validate_supported(
+                    "Vertical swing",
+                    &setting.vertical_swing,
+                    &capabilities.vertical_swing,
+                )?;
+                validate_supported(
+                    "Horizontal swing",
+                    &setting.horizontal_swing,
+                    &capabilities.horizontal_swing,
+                )?;
+                validate_supported("Swing", &setting.swing, &capabilities.swings)?;
+                validate_supported("Light", &setting.light, &capabilities.light)
+            }
+            _ => {
+                if setting.mode.is_some()
+                    || setting.fan_level.is_some()
+                    || setting.fan_speed.is_some()
+                    || setting.vertical_swing.is_some()
+                    || setting.horizontal_swing.is_some()
+                    || setting.swing.is_some()
+                    || setting.light.is_some()
+                {
+                    return Err(Error::InvalidInput(format!(
+                        "{} zones only accept power and temperature",
+                        self.r#type
+                    )));
+                }
+
+                match (&setting.temperature, &self.temperatures) {
+                    (Some(_), _) if self.can_set_temperature == Some(false) => Err(
+                        Error::InvalidInput("The zone does not accept a temperature".to_string()),
+                    ),
+                    (Some(temperature), Some(temperatures)) => {
+                        validate_range(temperature, &temperatures.celsius)
+                    }
+                    _ => setting.validate(),
+                }
+            }
+        }
+    }
+}
+
+new_type![ZoneFeatures {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dazzle: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open_window_detection: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open_window_detection_timeout_in_seconds: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    early_start: Option<bool>,
+}];
+
+new_type![ZoneName { name: String }];
+
+new_type![ZoneDevice {
+    serial_no: DeviceId
+}];
+
+new_type![ZoneCreation {
+    r#type: ZoneType,
+    devices: Vec<ZoneDevice>,
+}];
+
+new_type![ZoneInsideTemperature {
+    celsius: f32,
+    fahrenheit: f32,
+    timestamp: Date,
+}];
+
+new_type![ZoneHumidity {
+    percentage: f32,
+    timestamp: Date,
+}];
+
+new_type![ZoneSensorDataPoints {
+    inside_temperature: Option<ZoneInsideTemperature>,
+    humidity: Option<ZoneHumidity>,
+}];
+
+new_type![ZoneOpenWindow {
+    detected_time: Date,
+    duration_in_seconds: u32,
+    expiry: Date,
+    remaining_time_in_seconds: u32,
+}];
+
 new_type![ZoneState {
+    tado_mode: Option<StatePresence>,
     geolocation_override: Option<bool>,
     geolocation_override_disable_time: Option<bool>,
+    setting: Option<ZoneSetting>,
+    overlay: Option<Overlay>,
     open_window_detected: Option<bool>,
+    open_window: Option<ZoneOpenWindow>,
+    sensor_data_points: Option<ZoneSensorDataPoints>,
+}];
+
+impl ZoneState {
+    /// An open window was detected but open window mode was not activated yet
+    pub fn is_open_window_pending(&self) -> bool {
+        self.open_window_detected == Some(true) && self.open_window.is_none()
+    }
+
+    /// Whether the boiler is currently asked to produce hot water
+    pub fn is_producing_hot_water(&self) -> bool {
+        self.setting.as_ref().is_some_and(|setting| {
+            setting.r#type == ZoneType::HotWater && setting.power == Power::On
+        })
+    }
+}
+
+new_enum![TimetableType {
+    OneDay,
+    ThreeDay,
+    SevenDay,
+}];
+
+new_type![Timetable {
+    id: u32,
+    r#type: Option<TimetableType>,
+}];
+
+new_enum![DayType {
+    MondayToSunday,
+    MondayToFriday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}];
+
+new_type![ScheduleBlock {
+    day_type: DayType,
+    start: String,
+    end: String,
+    geolocation_override: bool,
+    setting: ZoneSetting,
 }];
 
 new_type![WeatherSolarIntensity {
@@ -351,8 +931,10 @@ new_type![AirComfort {
 
 new_type![HeatingSystemBoiler {
     present: bool,
-    id: u32,
-    found: bool,
+    /// Model of the boiler, as returned by the boiler search
+    id: Option<u32>,
+    brand_id: Option<u32>,
+    found: Option<bool>,
 }];
 
 new_type![HeatingSystemUnderfloorHeating { present: bool }];
@@ -362,6 +944,67 @@ new_type![HeatingSystem {
     underfloor_heating: HeatingSystemUnderfloorHeating,
 }];
 
+new_type![FlowTemperatureConstraints { min: f32, max: f32 }];
+
+new_type![FlowTemperatureAutoAdaptation {
+    enabled: bool,
+    max_flow_temperature: Option<f32>,
+}];
+
+new_type![FlowTemperatureOptimization {
+    max_flow_temperature: f32,
+    max_flow_temperature_constraints: FlowTemperatureConstraints,
+    auto_adaptation: FlowTemperatureAutoAdaptation,
+    open_therm_device_serial_number: Option<DeviceId>,
+}];
+
+impl FlowTemperatureOptimization {
+    pub fn validate_max_flow_temperature(&self, celsius: f32) -> Result<(), Error> {
+        let constraints = &self.max_flow_temperature_constraints;
+
+        if (constraints.min..=constraints.max).contains(&celsius) {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "Maximum flow temperature of {}°C is outside of the {}..={}°C range",
+                celsius, constraints.min, constraints.max
+            )))
+        }
+    }
+}
+
+new_type![BoilerBrand {
+    id: u32,
+    name: String,
+}];
+
+new_type![BoilerModel {
+    id: u32,
+    name: String,
+    brand: BoilerBrand,
+}];
+
+new_type![BoilerOutputTemperature {
+    celsius: f32,
+    timestamp: Date,
+}];
+
+new_type![BoilerState {
+    output_temperature: Option<BoilerOutputTemperature>,
+}];
+
+new_type![BoilerWiringInstallationState {
+    state: String,
+    ac_power_connected: Option<bool>,
+    bridge_connected: Option<bool>,
+    hot_water_zone_present: Option<bool>,
+    boiler: Option<BoilerState>,
+}];
+
+new_type![BoilerMaxOutputTemperature {
+    boiler_max_output_temperature_in_celsius: f32,
+}];
+
 new_enum![AwayConfigurationPreheatingLevel {
     Low,
     Medium,
@@ -370,6 +1013,136 @@ new_enum![AwayConfigurationPreheatingLevel {
 
 new_type![AwayConfiguration {
     r#type: ZoneType,
-    preheating_level: AwayConfigurationPreheatingLevel,
-    minimum_away_temperature: Temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preheating_level: Option<AwayConfigurationPreheatingLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minimum_away_temperature: Option<Temperature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auto_adjust: Option<bool>,
+    /// From 0 (eco) to 100 (comfort), 50 being the balanced level
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comfort_level: Option<u8>,
+    /// Used by hot water and air conditioning zones instead of a minimum temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    setting: Option<ZoneSetting>,
 }];
+
+impl AwayConfiguration {
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(temperature) = &self.minimum_away_temperature {
+            self.r#type.validate_temperature(temperature)?;
+        }
+
+        if let Some(level) = self.comfort_level.filter(|level| *level > 100) {
+            return Err(Error::InvalidInput(format!(
+                "{} is not a valid comfort level",
+                level
+            )));
+        }
+
+        if let Some(setting) = &self.setting {
+            if setting.r#type != self.r#type {
+                return Err(Error::InvalidInput(format!(
+                    "{} setting cannot be used for {} zones",
+                    setting.r#type, self.r#type
+                )));
+            }
+
+            setting.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+new_enum![EnergySavingsUnit {
+    Percentage,
+    Hours,
+    Days,
+}];
+
+new_type![EnergySavingsQuantity {
+    value: f32,
+    unit: EnergySavingsUnit,
+}];
+
+new_type![EnergySavingsInterval {
+    start: Date,
+    end: Date,
+}];
+
+new_type![EnergySavingsReport {
+    covered_interval: Option<EnergySavingsInterval>,
+    total_savings_available: bool,
+    total_savings: Option<EnergySavingsQuantity>,
+    total_savings_in_thermostatic_mode: Option<EnergySavingsQuantity>,
+    manual_control_saving: Option<EnergySavingsQuantity>,
+    away_duration: Option<EnergySavingsQuantity>,
+    open_window_detection_times: Option<u32>,
+    sunshine_duration: Option<EnergySavingsQuantity>,
+    setback_schedule_duration_per_day: Option<EnergySavingsQuantity>,
+    has_auto_assist: Option<bool>,
+}];
+
+new_type![EnergySavingsComparison {
+    current: EnergySavingsReport,
+    previous: EnergySavingsReport,
+}];
+
+impl EnergySavingsComparison {
+    /// Difference of the total savings between the current and the previous period,
+    /// available only when both reports express them in the same unit.
+    pub fn total_savings_change(&self) -> Option<f32> {
+        let current = self.current.total_savings.as_ref()?;
+        let previous = self.previous.total_savings.as_ref()?;
+
+        (current.unit == previous.unit).then_some(current.value - previous.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn keeps_unknown_installation_states() {
+        let state =
+            |value: &str| -> InstallationState { serde_json::from_value(json!(value)).unwrap() };
+
+        assert_eq!(state("COMPLETED"), InstallationState::Completed);
+        assert_eq!(state("INSTALLING"), InstallationState::Installing);
+        assert_eq!(
+            state("WAITING_FOR_BOILER"),
+            InstallationState::Other("WAITING_FOR_BOILER".to_string())
+        );
+        assert_eq!(
+            state("WAITING_FOR_BOILER").to_string(),
+            "WAITING_FOR_BOILER"
+        );
+        assert_eq!(
+            serde_json::to_value(InstallationState::Other("PAIRING".to_string())).unwrap(),
+            json!("PAIRING")
+        );
+    }
+
+    #[test]
+    fn derives_missing_fahrenheit() {
+        let temperature: Temperature = serde_json::from_value(json!({ "celsius": 20 })).unwrap();
+        assert_eq!(temperature.fahrenheit, 68.0);
+
+        let temperature: Temperature =
+            serde_json::from_value(json!({ "celsius": 20, "fahrenheit": 68.5 })).unwrap();
+        assert_eq!(temperature.fahrenheit, 68.5);
+    }
+
+    #[test]
+    fn reads_api_timestamps() {
+        let date: Date = serde_json::from_value(json!("2024-03-01T10:15:30.000Z")).unwrap();
+        assert_eq!(unix_seconds(&date), Some(1_709_288_130));
+
+        let date: Date = serde_json::from_value(json!("1969-12-31T23:59:59Z")).unwrap();
+        assert_eq!(unix_seconds(&date), Some(-1));
+    }
+}
@@ -0,0 +1,86 @@
+//! tado Care incidents, served by the minder service with the same authentication
+//! as the main API, and the home setting that turns their detection on or off.
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use strum::Display;
+
+use crate::{
+    cnf::{API_URL, MINDER_URL},
+    data::{new_enum, new_type, Date, DeviceId, Support, ZoneId},
+    Client, Error,
+};
+
+new_enum![IncidentSeverity {
+    Low,
+    Medium,
+    High,
+    Critical,
+    #[serde(other)]
+    Unknown,
+}];
+
+new_enum![IncidentStatus {
+    Open,
+    InProgress,
+    Resolved,
+    Closed,
+    #[serde(other)]
+    Unknown,
+}];
+
+new_type![IncidentDevice {
+    serial_no: DeviceId,
+    device_type: Option<String>,
+}];
+
+new_type![Incident {
+    id: String,
+    r#type: String,
+    severity: IncidentSeverity,
+    status: IncidentStatus,
+    zone_id: Option<ZoneId>,
+    device: Option<IncidentDevice>,
+    created_at: Date,
+    updated_at: Option<Date>,
+    resolved_at: Option<Date>,
+}];
+
+impl Incident {
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.status,
+            IncidentStatus::Open | IncidentStatus::InProgress
+        )
+    }
+}
+
+new_type![Incidents {
+    incidents: Vec<Incident>,
+}];
+
+impl Client {
+    pub async fn get_incidents(&self) -> Result<Vec<Incident>, Error> {
+        let url = format!("{}homes/{}/incidents", MINDER_URL, self.home_id().await?);
+        let incidents: Result<Incidents, Error> = response!(self, Method::GET, url);
+
+        Ok(incidents?.incidents)
+    }
+
+    pub async fn get_incident_detection(&self) -> Result<Support, Error> {
+        Ok(self.get_home().await?.incident_detection)
+    }
+
+    pub async fn set_incident_detection(&self, enabled: bool) -> Result<(), Error> {
+        let url = format!(
+            "{}homes/{}/incidentDetection",
+            API_URL,
+            self.home_id().await?
+        );
+
+        response!(@empty self, Method::PUT, url, {
+            "enabled": enabled
+        })
+    }
+}
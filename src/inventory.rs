@@ -0,0 +1,174 @@
+//! Aggregated view of the devices of a home, for fleet audits.
+
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use crate::{
+    data::{new_enum, new_type, Date, Device, DeviceBatteryState, DeviceId, ZoneId},
+    Client, Error,
+};
+
+new_enum![DeviceFamily {
+    SmartRadiatorThermostat,
+    SmartThermostat,
+    WirelessReceiver,
+    InternetBridge,
+    ExtensionKit,
+    WirelessTemperatureSensor,
+    SmartAcControl,
+    Unknown,
+}];
+
+impl DeviceFamily {
+    /// Decodes the family from device types such as VA02, RU02, BR02 or IB01
+    pub fn from_device_type(device_type: &str) -> Self {
+        match device_type.get(..2) {
+            Some("VA") => DeviceFamily::SmartRadiatorThermostat,
+            Some("RU") => DeviceFamily::SmartThermostat,
+            Some("BR") => DeviceFamily::WirelessReceiver,
+            Some("IB") => DeviceFamily::InternetBridge,
+            Some("BU") => DeviceFamily::ExtensionKit,
+            Some("SU") => DeviceFamily::WirelessTemperatureSensor,
+            Some("WR") => DeviceFamily::SmartAcControl,
+            _ => DeviceFamily::Unknown,
+        }
+    }
+}
+
+new_type![DeviceRecord {
+    serial_no: DeviceId,
+    short_serial_no: DeviceId,
+    device_type: String,
+    family: DeviceFamily,
+    /// Hardware revision, taken from the digits of the device type
+    revision: Option<u32>,
+    zone: Option<ZoneId>,
+    zone_name: Option<String>,
+    usage: Option<String>,
+    firmware: String,
+    battery: Option<DeviceBatteryState>,
+    online: bool,
+    last_seen: Date,
+}];
+
+impl DeviceRecord {
+    fn new(device: Device) -> Self {
+        Self {
+            family: DeviceFamily::from_device_type(&device.device_type),
+            revision: device
+                .device_type
+                .get(2..)
+                .and_then(|digits| digits.parse().ok()),
+            serial_no: device.serial_no,
+            short_serial_no: device.short_serial_no,
+            device_type: device.device_type,
+            zone: None,
+            zone_name: None,
+            usage: None,
+            firmware: device.current_fw_version,
+            battery: device.battery_state,
+            online: device.connection_state.value,
+            last_seen: device.connection_state.timestamp,
+        }
+    }
+}
+
+new_type![Inventory {
+    devices: Vec<DeviceRecord>,
+}];
+
+impl Inventory {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Renders the inventory as a plain text table, one device per line
+    pub fn to_table(&self) -> String {
+        let header = [
+            "SERIAL",
+            "TYPE",
+            "FAMILY",
+            "ZONE",
+            "FIRMWARE",
+            "BATTERY",
+            "CONNECTION",
+            "LAST SEEN",
+        ]
+        .map(String::from);
+
+        let rows = self.devices.iter().map(|device| {
+            [
+                device.serial_no.clone(),
+                device.device_type.clone(),
+                device.family.to_string(),
+                device.zone_name.clone().unwrap_or_else(|| "-".to_string()),
+                device.firmware.clone(),
+                device
+                    .battery
+                    .as_ref()
+                    .map_or_else(|| "-".to_string(), ToString::to_string),
+                match device.online {
+                    true => "ONLINE".to_string(),
+                    false => "OFFLINE".to_string(),
+                },
+                device.last_seen.to_string(),
+            ]
+        });
+
+        let lines: Vec<[String; 8]> = std::iter::once(header).chain(rows).collect();
+        let mut widths = [0; 8];
+
+        for line in &lines {
+            for (width, cell) in widths.iter_mut().zip(line) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        lines
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .zip(widths)
+                    .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Client {
+    /// Combines devices, their usage and the zones they are assigned to
+    pub async fn get_inventory(&self) -> Result<Inventory, Error> {
+        let zones = self.get_zones().await?;
+        let usage = self.get_device_usage().await?;
+        let mut devices: Vec<DeviceRecord> = self
+            .get_devices()
+            .await?
+            .into_iter()
+            .map(DeviceRecord::new)
+            .collect();
+
+        for device in devices.iter_mut() {
+            if let Some(zone) = zones.iter().find(|zone| {
+                zone.devices
+                    .iter()
+                    .any(|candidate| candidate.serial_no == device.serial_no)
+            }) {
+                device.zone = Some(zone.id);
+                device.zone_name = Some(zone.name.clone());
+            }
+
+            device.usage = usage
+                .entries
+                .iter()
+                .find(|entry| entry.device.serial_no == device.serial_no)
+                .map(|entry| entry.r#type.clone());
+        }
+
+        Ok(Inventory { devices })
+    }
+}
@@ -1,10 +1,15 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use cnf::{API_URL, AUTH_SCOPE, AUTH_URL};
+use cnf::{API_URL, AUTH_SCOPE, AUTH_URL, BOILER_URL, ENERGY_BOB_URL};
 use data::{
-    AirComfort, AwayConfiguration, Device, DeviceUsage, EarlyStart, HeatingCircuit, HeatingSystem,
-    Home, HomeState, Invitation, MobileDevice, MobileDeviceSettings, StatePresence, Temperature,
-    User, Weather, Zone, ZoneId, ZoneState,
+    AirComfort, AwayConfiguration, BoilerMaxOutputTemperature, BoilerModel,
+    BoilerWiringInstallationState, DayType, DefaultOverlay, Device, DeviceId, DeviceUsage,
+    EarlyStart, EnergySavingsComparison, EnergySavingsReport, FlowTemperatureOptimization,
+    HeatingCircuit, HeatingSystem, Home, HomeDetails, HomeGeneration, HomeId, HomeState,
+    Installation, Invitation, MobileDevice, MobileDeviceId, MobileDeviceSettings, Overlay,
+    OverlayTerminationCondition, Power, ScheduleBlock, StatePresence, Temperature, TemperatureUnit,
+    Timetable, User, Weather, Zone, ZoneCapabilities, ZoneCreation, ZoneDevice, ZoneFeatures,
+    ZoneId, ZoneName, ZoneSetting, ZoneState, ZoneType,
 };
 use oauth2::{
     basic::{BasicClient, BasicTokenType},
@@ -12,106 +17,30 @@ use oauth2::{
     AccessToken, AuthUrl, ClientId, ClientSecret, EmptyExtraTokenFields, ResourceOwnerPassword,
     ResourceOwnerUsername, Scope, StandardTokenResponse, TokenResponse, TokenUrl,
 };
-use reqwest::{Client as HttpClient, ClientBuilder, Method, RequestBuilder};
+use reqwest::{Client as HttpClient, ClientBuilder, Method, RequestBuilder, Url};
 use serde_json::{json, Value};
 use thiserror::Error;
 use tokio::sync::Mutex;
 
 use crate::cnf::{CLIENT_ID, CLIENT_SECRET};
 
+#[macro_use]
+mod macros;
+
+pub mod alert;
 mod cnf;
 pub mod data;
-
-macro_rules! response {
-    ($self:expr, $method:expr, $url:tt) => {{
-        let token = $self.token().await?;
-        let response = $self.build($method, &$url, &token).send().await?;
-
-        if response.status().is_success() {
-            Ok(response.json().await?)
-        } else {
-            Err(Error::UnsuccesfulOperation(response.json().await?))
-        }
-    }};
-
-    ($self:expr, $method:expr, $url:tt, $payload:tt) => {{
-        let payload = json!($payload);
-        let token = $self.token().await?;
-        let response = $self
-            .build($method, &$url, &token)
-            .json(&payload)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            Ok(response.json().await?)
-        } else {
-            Err(Error::UnsuccesfulOperation(response.json().await?))
-        }
-    }};
-}
-
-macro_rules! api {
-    ($name:ident, $data:ty, $path:literal) => {
-        pub async fn $name(&self) -> Result<$data, Error> {
-            let url = format!("{}{}", API_URL, $path);
-            let url = url.replace("{home}", &self.get_me().await?.homes[0].id.to_string());
-
-            response!(self, Method::GET, url)
-        }
-    };
-
-    ($name:ident, $data:ty, $path:literal $(, $dyn_param:ident: $dyn_type:ty)*) => {
-        pub async fn $name(&self $(, $dyn_param: $dyn_type)*) -> Result<$data, Error> {
-            let template = format!("{}{}", API_URL, $path);
-            let url = template.replace("{home}", &self.get_me().await?.homes[0].id.to_string());
-
-            $(let url = url.replace(concat!("{", stringify!($dyn_param), "}"), $dyn_param.to_string().as_str());)*
-
-            response!(self, Method::GET, url)
-        }
-    };
-
-    // Special case where we don't need to return anything
-    // So json deserialize is not needed otherwise it would fail
-    ($name:ident, $method:expr, $payload:tt, $path:literal $(, $dyn_param:ident: $dyn_type:ty)*) => {
-        pub async fn $name(&self $(, $dyn_param: $dyn_type)*) -> Result<(), Error> {
-            let payload = json!($payload);
-            let template = format!("{}{}", API_URL, $path);
-            let url = template.replace("{home}", &self.get_me().await?.homes[0].id.to_string());
-
-            $(let url = url.replace(concat!("{", stringify!($dyn_param), "}"), $dyn_param.to_string().as_str());)*
-
-            let token = self.token().await?;
-            let response = self
-                .build($method, &url, &token)
-                .json(&payload)
-                .send()
-                .await?;
-
-            if response.status().is_success() {
-                // Still consume the response
-                let _ = response.text().await?;
-
-                Ok(())
-            } else {
-                Err(Error::UnsuccesfulOperation(response.json().await?))
-            }
-        }
-    };
-
-    ($name:ident, $data:ty, $method:expr, $payload:tt, $path:literal $(, $dyn_param:ident: $dyn_type:ty)*) => {
-        pub async fn $name(&self $(, $dyn_param: $dyn_type)*) -> Result<$data, Error> {
-            let payload = json!($payload);
-            let template = format!("{}{}", API_URL, $path);
-            let url = template.replace("{home}", &self.get_me().await?.homes[0].id.to_string());
-
-            $(let url = url.replace(concat!("{", stringify!($dyn_param), "}"), $dyn_param.to_string().as_str());)*
-
-            response!(self, $method, url, payload)
-        }
-    };
-}
+pub mod incident;
+pub mod inventory;
+pub mod presence;
+pub mod reconcile;
+pub mod room;
+pub mod rules;
+pub mod schedule;
+pub mod snapshot;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+pub mod x;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -121,6 +50,11 @@ pub enum Error {
     InvalidAuth,
     #[error("Failed to execute operation: {0}")]
     UnsuccesfulOperation(Value),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[cfg(feature = "webhook")]
+    #[error("Failed to deliver to {}", .0.iter().map(|(url, error)| format!("{} ({})", url, error)).collect::<Vec<_>>().join(", "))]
+    Delivery(Vec<(String, reqwest::Error)>),
 }
 
 pub struct Auth {
@@ -145,11 +79,26 @@ pub struct Client {
     configuration: Configuration,
 }
 
+// Wiring endpoints are addressed by bridge and authorized by the key printed on it
+fn bridge_url(bridge: &DeviceId, endpoint: &str, auth_key: &str) -> String {
+    Url::parse_with_params(
+        &format!("{}homeByBridge/{}/{}", API_URL, bridge, endpoint),
+        &[("authKey", auth_key)],
+    )
+    // Safe to unwrap as we know the URL is correct
+    .unwrap()
+    .to_string()
+}
+
 impl Client {
     fn build(&self, method: Method, url: &str, token: &AccessToken) -> RequestBuilder {
         self.inner.request(method, url).bearer_auth(token.secret())
     }
 
+    async fn home_id(&self) -> Result<HomeId, Error> {
+        Ok(self.get_me().await?.homes[0].id)
+    }
+
     async fn token(&self) -> Result<AccessToken, Error> {
         let mut session = self.session.lock().await;
         match session.as_mut() {
@@ -241,10 +190,115 @@ impl Client {
 
     api!(get_home, Home, "homes/{home}");
 
+    pub async fn set_home_details(&self, details: &HomeDetails) -> Result<Home, Error> {
+        if details.name.trim().is_empty() {
+            return Err(Error::InvalidInput("Home name cannot be empty".to_string()));
+        }
+
+        self.update_home("details", json!(details)).await
+    }
+
+    pub async fn set_away_radius(&self, meters: f32) -> Result<Home, Error> {
+        if !Home::AWAY_RADIUS_RANGE.contains(&meters) {
+            return Err(Error::InvalidInput(format!(
+                "Away radius of {}m is outside of the {}..={}m range",
+                meters,
+                Home::AWAY_RADIUS_RANGE.start(),
+                Home::AWAY_RADIUS_RANGE.end()
+            )));
+        }
+
+        self.update_home(
+            "awayRadiusInMeters",
+            json!({
+                "awayRadiusInMeters": meters
+            }),
+        )
+        .await
+    }
+
+    pub async fn set_temperature_unit(&self, unit: TemperatureUnit) -> Result<Home, Error> {
+        self.update_home(
+            "temperatureUnit",
+            json!({
+                "temperatureUnit": unit
+            }),
+        )
+        .await
+    }
+
+    pub async fn set_christmas_mode(&self, enabled: bool) -> Result<Home, Error> {
+        self.update_home(
+            "christmasMode",
+            json!({
+                "enabled": enabled
+            }),
+        )
+        .await
+    }
+
+    // Home settings endpoints don't answer with the home, so fetch it again
+    async fn update_home(&self, setting: &str, payload: Value) -> Result<Home, Error> {
+        let url = format!("{}homes/{}/{}", API_URL, self.home_id().await?, setting);
+        let result: Result<(), Error> = response!(@empty self, Method::PUT, url, payload);
+
+        result?;
+        self.get_home().await
+    }
+
+    pub async fn get_home_generation(&self) -> Result<HomeGeneration, Error> {
+        Ok(self
+            .get_home()
+            .await?
+            .generation
+            .unwrap_or(HomeGeneration::PreLineX))
+    }
+
     api!(get_home_state, HomeState, "homes/{home}/state");
 
     api!(get_weather, Weather, "homes/{home}/weather");
 
+    /// Savings report of the given month, served by the energy service instead of the main API
+    pub async fn get_energy_savings_report(
+        &self,
+        year: i32,
+        month: u32,
+    ) -> Result<EnergySavingsReport, Error> {
+        if !(1..=12).contains(&month) {
+            return Err(Error::InvalidInput(format!(
+                "{} is not a valid month",
+                month
+            )));
+        }
+
+        let home = self.get_home().await?;
+        let url = format!(
+            "{}{}/{:04}-{:02}?country={}",
+            ENERGY_BOB_URL, home.basic.id, year, month, home.address.country
+        );
+
+        response!(self, Method::GET, url)
+    }
+
+    /// Savings report of the given month together with the one of the month before
+    pub async fn get_energy_savings_comparison(
+        &self,
+        year: i32,
+        month: u32,
+    ) -> Result<EnergySavingsComparison, Error> {
+        let (previous_year, previous_month) = match month {
+            1 => (year - 1, 12),
+            _ => (year, month.saturating_sub(1)),
+        };
+
+        Ok(EnergySavingsComparison {
+            current: self.get_energy_savings_report(year, month).await?,
+            previous: self
+                .get_energy_savings_report(previous_year, previous_month)
+                .await?,
+        })
+    }
+
     api!(get_devices, Vec<Device>, "homes/{home}/devices");
 
     api!(get_device_usage, DeviceUsage, "homes/{home}/deviceList");
@@ -270,16 +324,80 @@ impl Client {
         device: String
     );
 
+    pub async fn set_mobile_device_settings(
+        &self,
+        device: &MobileDeviceId,
+        settings: &MobileDeviceSettings,
+    ) -> Result<MobileDeviceSettings, Error> {
+        let url = format!(
+            "{}homes/{}/mobileDevices/{}/settings",
+            API_URL,
+            self.home_id().await?,
+            device
+        );
+
+        response!(self, Method::PUT, url, settings)
+    }
+
+    api!(delete_mobile_device, Method::DELETE, null, "homes/{home}/mobileDevices/{device}", device: &MobileDeviceId);
+
+    /// Mobile devices whose location currently decides if the home is in HOME or AWAY mode
+    pub async fn get_presence_deciding_devices(&self) -> Result<Vec<MobileDevice>, Error> {
+        Ok(self
+            .get_mobile_devices()
+            .await?
+            .into_iter()
+            .filter(MobileDevice::decides_presence)
+            .collect())
+    }
+
     api!(get_users, Vec<User>, "homes/{home}/users");
 
     api!(get_zones, Vec<Zone>, "homes/{home}/zones");
 
+    pub async fn get_zone(&self, zone: &ZoneId) -> Result<Zone, Error> {
+        self.get_zones()
+            .await?
+            .into_iter()
+            .find(|candidate| candidate.id == *zone)
+            .ok_or_else(|| Error::InvalidInput(format!("Zone {} does not exist", zone)))
+    }
+
     api!(get_early_start, EarlyStart, "homes/{home}/zones/{zone}/earlyStart", zone: &ZoneId);
 
     api!(set_early_start, EarlyStart, Method::PUT, {
         "enabled": enabled
     }, "homes/{home}/zones/{zone}/earlyStart", zone: &ZoneId, enabled: bool);
 
+    api!(
+        get_zone_capabilities,
+        ZoneCapabilities,
+        "homes/{home}/zones/{zone}/capabilities",
+        zone: &ZoneId
+    );
+
+    api!(get_overlay, Overlay, "homes/{home}/zones/{zone}/overlay", zone: &ZoneId);
+
+    /// Starts manual control, rejecting settings the zone capabilities don't support
+    pub async fn set_overlay(&self, zone: &ZoneId, overlay: &Overlay) -> Result<Overlay, Error> {
+        self.get_zone_capabilities(zone)
+            .await?
+            .validate(&overlay.setting)?;
+
+        self.put_overlay(zone, overlay).await
+    }
+
+    async fn put_overlay(&self, zone: &ZoneId, overlay: &Overlay) -> Result<Overlay, Error> {
+        let url = format!(
+            "{}homes/{}/zones/{}/overlay",
+            API_URL,
+            self.home_id().await?,
+            zone
+        );
+
+        response!(self, Method::PUT, url, overlay)
+    }
+
     api!(end_manual_control, Method::DELETE, null, "homes/{home}/zones/{zone}/overlay", zone: &ZoneId);
 
     api!(get_zone_states, Vec<ZoneState>, "homes/{home}/zoneStates");
@@ -290,16 +408,28 @@ impl Client {
         "homes/{home}/heatingCircuits"
     );
 
-    // TODO: Type unknown
-    // TODO: Use minder API
-    api!(get_incidents, Value, "homes/{home}/incidents");
+    api!(
+        get_installations,
+        Vec<Installation>,
+        "homes/{home}/installations"
+    );
 
-    api!(set_incident_detection, Method::PUT, {
-        "enabled": enabled
-    }, "homes/{home}/incidentDetection", enabled: bool);
+    #[deprecated(note = "Use `get_installations` instead")]
+    pub async fn get_installtions(&self) -> Result<Value, Error> {
+        let url = format!("{}homes/{}/installations", API_URL, self.home_id().await?);
 
-    // TODO: Type unknown
-    api!(get_installtions, Value, "homes/{home}/installations");
+        response!(self, Method::GET, url)
+    }
+
+    /// Installations that were started but never completed, useful to troubleshoot new homes
+    pub async fn get_unfinished_installations(&self) -> Result<Vec<Installation>, Error> {
+        Ok(self
+            .get_installations()
+            .await?
+            .into_iter()
+            .filter(|installation| !installation.is_completed())
+            .collect())
+    }
 
     api!(get_air_comfort, AirComfort, "homes/{home}/airComfort");
 
@@ -309,6 +439,148 @@ impl Client {
         "homes/{home}/heatingSystem"
     );
 
+    /// Searches the boiler models known by tado, to be matched with the home
+    pub async fn search_boilers(&self, query: &str) -> Result<Vec<BoilerModel>, Error> {
+        let url = Url::parse_with_params(&format!("{}boilers", BOILER_URL), &[("search", query)])
+            // Safe to unwrap as we know the URL is correct
+            .unwrap()
+            .to_string();
+
+        response!(self, Method::GET, url)
+    }
+
+    /// Sets the boiler model of the home, as found with the boiler search
+    pub async fn match_boiler(&self, model: &BoilerModel) -> Result<HeatingSystem, Error> {
+        self.update_heating_system(
+            "boiler",
+            json!({
+                "present": true,
+                "found": true,
+                "id": model.id,
+            }),
+        )
+        .await
+    }
+
+    /// Marks the boiler as present without a known model, or as missing
+    pub async fn set_boiler_present(&self, present: bool) -> Result<HeatingSystem, Error> {
+        self.update_heating_system(
+            "boiler",
+            json!({
+                "present": present,
+                "found": false,
+            }),
+        )
+        .await
+    }
+
+    pub async fn set_underfloor_heating(&self, present: bool) -> Result<HeatingSystem, Error> {
+        self.update_heating_system(
+            "underfloorHeating",
+            json!({
+                "present": present
+            }),
+        )
+        .await
+    }
+
+    async fn update_heating_system(
+        &self,
+        part: &str,
+        payload: Value,
+    ) -> Result<HeatingSystem, Error> {
+        let url = format!(
+            "{}homes/{}/heatingSystem/{}",
+            API_URL,
+            self.home_id().await?,
+            part
+        );
+        let result: Result<(), Error> = response!(@empty self, Method::PUT, url, payload);
+
+        result?;
+        self.get_heating_system().await
+    }
+
+    api!(
+        get_flow_temperature_optimization,
+        FlowTemperatureOptimization,
+        "homes/{home}/flowTemperatureOptimization"
+    );
+
+    pub async fn set_max_flow_temperature(
+        &self,
+        celsius: f32,
+    ) -> Result<FlowTemperatureOptimization, Error> {
+        self.get_flow_temperature_optimization()
+            .await?
+            .validate_max_flow_temperature(celsius)?;
+
+        self.update_flow_temperature_optimization(json!({
+            "maxFlowTemperature": celsius
+        }))
+        .await
+    }
+
+    pub async fn set_flow_temperature_auto_adaptation(
+        &self,
+        enabled: bool,
+    ) -> Result<FlowTemperatureOptimization, Error> {
+        self.update_flow_temperature_optimization(json!({
+            "autoAdaptation": {
+                "enabled": enabled
+            }
+        }))
+        .await
+    }
+
+    async fn update_flow_temperature_optimization(
+        &self,
+        payload: Value,
+    ) -> Result<FlowTemperatureOptimization, Error> {
+        let url = format!(
+            "{}homes/{}/flowTemperatureOptimization",
+            API_URL,
+            self.home_id().await?
+        );
+        let result: Result<(), Error> = response!(@empty self, Method::PATCH, url, payload);
+
+        result?;
+        self.get_flow_temperature_optimization().await
+    }
+
+    pub async fn get_boiler_wiring_installation_state(
+        &self,
+        bridge: &DeviceId,
+        auth_key: &str,
+    ) -> Result<BoilerWiringInstallationState, Error> {
+        let url = bridge_url(bridge, "boilerWiringInstallationState", auth_key);
+
+        response!(self, Method::GET, url)
+    }
+
+    pub async fn get_boiler_max_output_temperature(
+        &self,
+        bridge: &DeviceId,
+        auth_key: &str,
+    ) -> Result<BoilerMaxOutputTemperature, Error> {
+        let url = bridge_url(bridge, "boilerMaxOutputTemperature", auth_key);
+
+        response!(self, Method::GET, url)
+    }
+
+    pub async fn set_boiler_max_output_temperature(
+        &self,
+        bridge: &DeviceId,
+        auth_key: &str,
+        celsius: f32,
+    ) -> Result<(), Error> {
+        let url = bridge_url(bridge, "boilerMaxOutputTemperature", auth_key);
+
+        response!(@empty self, Method::PUT, url, {
+            "boilerMaxOutputTemperatureInCelsius": celsius
+        })
+    }
+
     api!(
         get_temperature_offset,
         Temperature,
@@ -335,36 +607,413 @@ impl Client {
         zone: &ZoneId
     );
 
+    pub async fn set_away_configuration(
+        &self,
+        zone: &ZoneId,
+        configuration: &AwayConfiguration,
+    ) -> Result<(), Error> {
+        configuration.validate()?;
+
+        let url = format!(
+            "{}homes/{}/zones/{}/awayConfiguration",
+            API_URL,
+            self.home_id().await?,
+            zone
+        );
+
+        response!(@empty self, Method::PUT, url, configuration)
+    }
+
     api!(set_open_window_detection, Method::PUT, {
         "enabled": enabled,
         "timeoutInSeconds": timeout,
     }, "homes/{home}/zones/{zone}/openWindowDetection", zone: &ZoneId, enabled: bool, timeout: u32);
 
-    // TODO: Type unknown
+    api!(set_dazzle_mode, Method::PUT, {
+        "enabled": enabled
+    }, "homes/{home}/zones/{zone}/dazzle", zone: &ZoneId, enabled: bool);
+
+    pub async fn get_zone_features(&self, zone: &ZoneId) -> Result<ZoneFeatures, Error> {
+        let details = self.get_zone(zone).await?;
+
+        Ok(ZoneFeatures {
+            dazzle: Some(details.dazzle_mode.enabled),
+            open_window_detection: Some(details.open_window_detection.enabled),
+            open_window_detection_timeout_in_seconds: Some(
+                details.open_window_detection.timeout_in_seconds,
+            ),
+            early_start: Some(self.get_early_start(zone).await?.enabled),
+        })
+    }
+
+    /// Applies the desired features, only calling the API for the ones that differ.
+    /// Returns the features that were actually changed.
+    pub async fn apply_zone_features(
+        &self,
+        zone: &ZoneId,
+        desired: &ZoneFeatures,
+    ) -> Result<ZoneFeatures, Error> {
+        let details = self.get_zone(zone).await?;
+        let mut changed = ZoneFeatures {
+            dazzle: None,
+            open_window_detection: None,
+            open_window_detection_timeout_in_seconds: None,
+            early_start: None,
+        };
+
+        let current = &details.open_window_detection;
+        let enabled = desired.open_window_detection.unwrap_or(current.enabled);
+        let timeout = desired
+            .open_window_detection_timeout_in_seconds
+            .unwrap_or(current.timeout_in_seconds);
+        let open_window_changed =
+            enabled != current.enabled || timeout != current.timeout_in_seconds;
+
+        // Every feature is checked before the first write, so nothing is applied halfway
+        if desired.dazzle == Some(true) && !details.dazzle_mode.supported {
+            return Err(Error::InvalidInput(format!(
+                "Zone {} does not support dazzle mode",
+                zone
+            )));
+        }
+
+        if open_window_changed && !current.supported {
+            return Err(Error::InvalidInput(format!(
+                "Zone {} does not support open window detection",
+                zone
+            )));
+        }
+
+        if let Some(enabled) = desired.dazzle {
+            if enabled != details.dazzle_mode.enabled {
+                self.set_dazzle_mode(zone, enabled).await?;
+                changed.dazzle = Some(enabled);
+            }
+        }
+
+        if open_window_changed {
+            self.set_open_window_detection(zone, enabled, timeout)
+                .await?;
+            changed.open_window_detection = Some(enabled);
+            changed.open_window_detection_timeout_in_seconds = Some(timeout);
+        }
+
+        if let Some(enabled) = desired.early_start {
+            if enabled != self.get_early_start(zone).await?.enabled {
+                self.set_early_start(zone, enabled).await?;
+                changed.early_start = Some(enabled);
+            }
+        }
+
+        Ok(changed)
+    }
+
+    api!(activate_open_window, Method::POST, null, "homes/{home}/zones/{zone}/state/openWindow/activate", zone: &ZoneId);
+
+    api!(deactivate_open_window, Method::DELETE, null, "homes/{home}/zones/{zone}/state/openWindow", zone: &ZoneId);
+
+    /// Activates open window mode in the zones where a detected open window
+    /// is still waiting for a confirmation, returning these zones
+    pub async fn activate_detected_open_windows(&self) -> Result<Vec<ZoneId>, Error> {
+        let mut activated = Vec::new();
+
+        for zone in self.get_zones().await? {
+            if self.get_state(&zone.id).await?.is_open_window_pending() {
+                self.activate_open_window(&zone.id).await?;
+                activated.push(zone.id);
+            }
+        }
+
+        Ok(activated)
+    }
+
     api!(
         get_default_overlay,
-        Value,
+        DefaultOverlay,
         "homes/{home}/zones/{zone}/defaultOverlay",
         zone: &ZoneId
     );
 
-    api!(get_measuring_device, String, "homes/{home}/zones/{zone}/measuringDevice", zone: &ZoneId);
+    pub async fn set_default_overlay(
+        &self,
+        zone: &ZoneId,
+        overlay: &DefaultOverlay,
+    ) -> Result<DefaultOverlay, Error> {
+        let url = format!(
+            "{}homes/{}/zones/{}/defaultOverlay",
+            API_URL,
+            self.home_id().await?,
+            zone
+        );
+
+        response!(self, Method::PUT, url, overlay)
+    }
+
+    /// Applies the same default overlay to every zone of the home
+    pub async fn set_default_overlay_for_all_zones(
+        &self,
+        overlay: &DefaultOverlay,
+    ) -> Result<Vec<(ZoneId, DefaultOverlay)>, Error> {
+        let mut overlays = Vec::new();
+
+        for zone in self.get_zones().await? {
+            let applied = self.set_default_overlay(&zone.id, overlay).await?;
+            overlays.push((zone.id, applied));
+        }
+
+        Ok(overlays)
+    }
+
+    api!(get_measuring_device, Device, "homes/{home}/zones/{zone}/measuringDevice", zone: &ZoneId);
+
+    /// Devices of the zone able to measure its temperature
+    pub async fn get_measuring_device_candidates(
+        &self,
+        zone: &ZoneId,
+    ) -> Result<Vec<Device>, Error> {
+        Ok(self
+            .get_zone(zone)
+            .await?
+            .devices
+            .into_iter()
+            .filter(Device::can_measure_temperature)
+            .collect())
+    }
+
+    pub async fn set_measuring_device(
+        &self,
+        zone: &ZoneId,
+        device: &DeviceId,
+    ) -> Result<(), Error> {
+        let candidate = self
+            .get_zone(zone)
+            .await?
+            .devices
+            .into_iter()
+            .find(|candidate| candidate.serial_no == *device)
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("Device {} is not part of zone {}", device, zone))
+            })?;
+
+        if !candidate.can_measure_temperature() {
+            return Err(Error::InvalidInput(format!(
+                "Device {} cannot measure temperature",
+                device
+            )));
+        }
+
+        let url = format!(
+            "{}homes/{}/zones/{}/measuringDevice",
+            API_URL,
+            self.home_id().await?,
+            zone
+        );
+
+        response!(@empty self, Method::PUT, url, {
+            "serialNo": device
+        })
+    }
 
     api!(get_state, ZoneState, "homes/{home}/zones/{zone}/state", zone: &ZoneId);
 
-    // TODO: Add return type
-    api!(set_zone_name, (), Method::PUT, {
+    api!(set_zone_name, ZoneName, Method::PUT, {
         "name": name
     }, "homes/{home}/zones/{zone}/name", zone: &ZoneId, name: String);
 
-    api!(get_schedule, String, "homes/{home}/zones/{zone}/schedule/activeTimetable", zone: &ZoneId);
+    pub async fn create_zone(
+        &self,
+        zone_type: ZoneType,
+        devices: &[DeviceId],
+    ) -> Result<Zone, Error> {
+        if devices.is_empty() {
+            return Err(Error::InvalidInput(
+                "A zone needs at least one device".to_string(),
+            ));
+        }
+
+        let creation = ZoneCreation {
+            r#type: zone_type,
+            devices: devices
+                .iter()
+                .map(|device| ZoneDevice {
+                    serial_no: device.clone(),
+                })
+                .collect(),
+        };
+
+        let url = format!("{}homes/{}/zones", API_URL, self.home_id().await?);
+
+        response!(self, Method::POST, url, creation)
+    }
+
+    /// Moves a device to another zone, returning the updated destination zone
+    pub async fn move_device(&self, device: &DeviceId, zone: &ZoneId) -> Result<Zone, Error> {
+        let zones = self.get_zones().await?;
+        let destination = zones
+            .iter()
+            .find(|candidate| candidate.id == *zone)
+            .ok_or_else(|| Error::InvalidInput(format!("Zone {} does not exist", zone)))?;
+
+        if destination
+            .devices
+            .iter()
+            .any(|candidate| candidate.serial_no == *device)
+        {
+            return Err(Error::InvalidInput(format!(
+                "Device {} is already part of zone {}",
+                device, zone
+            )));
+        }
+
+        if !zones
+            .iter()
+            .flat_map(|candidate| &candidate.devices)
+            .any(|candidate| candidate.serial_no == *device)
+        {
+            return Err(Error::InvalidInput(format!(
+                "Device {} is not assigned to any zone",
+                device
+            )));
+        }
+
+        let url = format!(
+            "{}homes/{}/zones/{}/devices",
+            API_URL,
+            self.home_id().await?,
+            zone
+        );
+
+        let result: Result<(), Error> = response!(@empty self, Method::POST, url, {
+            "serialNo": device
+        });
+
+        result?;
+        self.get_zone(zone).await
+    }
+
+    api!(delete_zone, Method::DELETE, null, "homes/{home}/zones/{zone}", zone: &ZoneId);
 
-    // TODO: Add return type
-    api!(set_schedule, (), Method::PUT, {
+    api!(get_schedule, Timetable, "homes/{home}/zones/{zone}/schedule/activeTimetable", zone: &ZoneId);
+
+    api!(set_schedule, Timetable, Method::PUT, {
         "id": schedule
     }, "homes/{home}/zones/{zone}/schedule/activeTimetable", zone: &ZoneId, schedule: u32);
 
-    api!(get_schedule_timetables, String, "homes/{home}/zones/{zone}/schedule/timetables", zone: &u32);
+    api!(get_schedule_timetables, Vec<Timetable>, "homes/{home}/zones/{zone}/schedule/timetables", zone: &u32);
+
+    api!(
+        get_schedule_blocks,
+        Vec<ScheduleBlock>,
+        "homes/{home}/zones/{zone}/schedule/timetables/{timetable}/blocks",
+        zone: &ZoneId,
+        timetable: u32
+    );
+
+    /// Replaces the blocks of one day type of the timetable
+    pub async fn set_schedule_blocks(
+        &self,
+        zone: &ZoneId,
+        timetable: u32,
+        day_type: &DayType,
+        blocks: &[ScheduleBlock],
+    ) -> Result<Vec<ScheduleBlock>, Error> {
+        if let Some(block) = blocks.iter().find(|block| block.day_type != *day_type) {
+            return Err(Error::InvalidInput(format!(
+                "Block starting at {} belongs to {} instead of {}",
+                block.start, block.day_type, day_type
+            )));
+        }
+
+        for block in blocks {
+            block.setting.validate()?;
+        }
+
+        let url = format!(
+            "{}homes/{}/zones/{}/schedule/timetables/{}/blocks/{}",
+            API_URL,
+            self.home_id().await?,
+            zone,
+            timetable,
+            day_type
+        );
+
+        response!(self, Method::PUT, url, blocks)
+    }
+
+    /// Blocks of the timetable currently followed by the zone
+    pub async fn get_active_schedule_blocks(
+        &self,
+        zone: &ZoneId,
+    ) -> Result<Vec<ScheduleBlock>, Error> {
+        let timetable = self.get_schedule(zone).await?;
+
+        self.get_schedule_blocks(zone, timetable.id).await
+    }
+
+    /// Turns hot water on for the given duration, at the given temperature or at the
+    /// temperature currently set on the zone when none
+    pub async fn boost_hot_water(
+        &self,
+        zone: &ZoneId,
+        duration: Duration,
+        temperature: Option<f32>,
+    ) -> Result<Overlay, Error> {
+        let termination = OverlayTerminationCondition::timer(duration)?;
+
+        let capabilities = self.get_hot_water_capabilities(zone).await?;
+        let temperature = match (capabilities.can_set_temperature, temperature) {
+            (Some(true), Some(celsius)) => Some(Temperature::from_celsius(celsius)),
+            (Some(true), None) => self
+                .get_state(zone)
+                .await?
+                .setting
+                .and_then(|setting| setting.temperature),
+            _ => None,
+        };
+
+        let overlay = Overlay {
+            setting: ZoneSetting::new(ZoneType::HotWater, Power::On, temperature),
+            termination,
+        };
+
+        capabilities.validate(&overlay.setting)?;
+        self.put_overlay(zone, &overlay).await
+    }
+
+    /// Turns hot water on or off, the temperature is only sent when the zone supports it
+    pub async fn set_hot_water(
+        &self,
+        zone: &ZoneId,
+        power: Power,
+        temperature: Option<f32>,
+        termination: OverlayTerminationCondition,
+    ) -> Result<Overlay, Error> {
+        let capabilities = self.get_hot_water_capabilities(zone).await?;
+        let temperature = match (&power, capabilities.can_set_temperature) {
+            (Power::On, Some(true)) => temperature.map(Temperature::from_celsius),
+            _ => None,
+        };
+
+        let overlay = Overlay {
+            setting: ZoneSetting::new(ZoneType::HotWater, power, temperature),
+            termination,
+        };
+
+        capabilities.validate(&overlay.setting)?;
+        self.put_overlay(zone, &overlay).await
+    }
+
+    async fn get_hot_water_capabilities(&self, zone: &ZoneId) -> Result<ZoneCapabilities, Error> {
+        let capabilities = self.get_zone_capabilities(zone).await?;
+
+        match capabilities.r#type {
+            ZoneType::HotWater => Ok(capabilities),
+            _ => Err(Error::InvalidInput(format!(
+                "Zone {} is not a hot water zone",
+                zone
+            ))),
+        }
+    }
 
     api!(set_presence, Method::PUT, {
         "homePresence": presence
@@ -0,0 +1,95 @@
+macro_rules! response {
+    // Operations that don't return anything
+    // So json deserialize is not needed otherwise it would fail
+    (@empty $self:expr, $method:expr, $url:tt, $payload:tt) => {{
+        let payload = json!($payload);
+        let token = $self.token().await?;
+        let response = $self
+            .build($method, &$url, &token)
+            .json(&payload)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            // Still consume the response
+            let _ = response.text().await?;
+
+            Ok(())
+        } else {
+            Err(Error::UnsuccesfulOperation(response.json().await?))
+        }
+    }};
+
+    ($self:expr, $method:expr, $url:tt) => {{
+        let token = $self.token().await?;
+        let response = $self.build($method, &$url, &token).send().await?;
+
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            Err(Error::UnsuccesfulOperation(response.json().await?))
+        }
+    }};
+
+    ($self:expr, $method:expr, $url:tt, $payload:tt) => {{
+        let payload = json!($payload);
+        let token = $self.token().await?;
+        let response = $self
+            .build($method, &$url, &token)
+            .json(&payload)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            Err(Error::UnsuccesfulOperation(response.json().await?))
+        }
+    }};
+}
+
+macro_rules! api {
+    ($name:ident, $data:ty, $path:literal) => {
+        pub async fn $name(&self) -> Result<$data, Error> {
+            let url = format!("{}{}", API_URL, $path);
+            let url = url.replace("{home}", &self.get_me().await?.homes[0].id.to_string());
+
+            response!(self, Method::GET, url)
+        }
+    };
+
+    ($name:ident, $data:ty, $path:literal $(, $dyn_param:ident: $dyn_type:ty)*) => {
+        pub async fn $name(&self $(, $dyn_param: $dyn_type)*) -> Result<$data, Error> {
+            let template = format!("{}{}", API_URL, $path);
+            let url = template.replace("{home}", &self.get_me().await?.homes[0].id.to_string());
+
+            $(let url = url.replace(concat!("{", stringify!($dyn_param), "}"), $dyn_param.to_string().as_str());)*
+
+            response!(self, Method::GET, url)
+        }
+    };
+
+    // Special case where we don't need to return anything
+    ($name:ident, $method:expr, $payload:tt, $path:literal $(, $dyn_param:ident: $dyn_type:ty)*) => {
+        pub async fn $name(&self $(, $dyn_param: $dyn_type)*) -> Result<(), Error> {
+            let template = format!("{}{}", API_URL, $path);
+            let url = template.replace("{home}", &self.get_me().await?.homes[0].id.to_string());
+
+            $(let url = url.replace(concat!("{", stringify!($dyn_param), "}"), $dyn_param.to_string().as_str());)*
+
+            response!(@empty self, $method, url, $payload)
+        }
+    };
+
+    ($name:ident, $data:ty, $method:expr, $payload:tt, $path:literal $(, $dyn_param:ident: $dyn_type:ty)*) => {
+        pub async fn $name(&self $(, $dyn_param: $dyn_type)*) -> Result<$data, Error> {
+            let payload = json!($payload);
+            let template = format!("{}{}", API_URL, $path);
+            let url = template.replace("{home}", &self.get_me().await?.homes[0].id.to_string());
+
+            $(let url = url.replace(concat!("{", stringify!($dyn_param), "}"), $dyn_param.to_string().as_str());)*
+
+            response!(self, $method, url, payload)
+        }
+    };
+}
@@ -0,0 +1,270 @@
+//! Drives the home presence from local signals (e.g. devices seen on the network)
+//! instead of the geofencing of the tado app.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{data::StatePresence, Client, Error};
+
+/// Tells whether someone is currently at home
+pub trait PresenceSource {
+    fn is_present(&mut self, now: Instant) -> bool;
+}
+
+/// Considers the home occupied while at least one device was seen recently
+pub struct SeenDevices {
+    timeout: Duration,
+    last_seen: HashMap<String, Instant>,
+}
+
+impl SeenDevices {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    pub fn seen(&mut self, device: impl Into<String>) {
+        self.seen_at(device, Instant::now());
+    }
+
+    pub fn seen_at(&mut self, device: impl Into<String>, at: Instant) {
+        self.last_seen.insert(device.into(), at);
+    }
+
+    pub fn forget(&mut self, device: &str) {
+        self.last_seen.remove(device);
+    }
+}
+
+impl PresenceSource for SeenDevices {
+    fn is_present(&mut self, now: Instant) -> bool {
+        let timeout = self.timeout;
+        self.last_seen
+            .retain(|_, seen| now.saturating_duration_since(*seen) < timeout);
+
+        !self.last_seen.is_empty()
+    }
+}
+
+pub struct PresenceConfiguration {
+    /// How long the source must report someone at home before switching to HOME
+    pub arrival_delay: Duration,
+    /// How long the source must report nobody at home before switching to AWAY
+    pub minimum_away: Duration,
+}
+
+impl Default for PresenceConfiguration {
+    fn default() -> Self {
+        Self {
+            arrival_delay: Duration::from_secs(30),
+            minimum_away: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+pub struct PresenceDetector<S> {
+    source: S,
+    configuration: PresenceConfiguration,
+    current: Option<StatePresence>,
+    pending: Option<(StatePresence, Instant)>,
+}
+
+impl<S: PresenceSource> PresenceDetector<S> {
+    pub fn new(source: S, configuration: PresenceConfiguration) -> Self {
+        Self {
+            source,
+            configuration,
+            current: None,
+            pending: None,
+        }
+    }
+
+    pub fn source(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Presence last applied by the detector, none while tado decides on its own
+    pub fn presence(&self) -> Option<&StatePresence> {
+        self.current.as_ref()
+    }
+
+    /// Starts from the presence currently locked on the home, if any
+    pub async fn sync(&mut self, client: &Client) -> Result<(), Error> {
+        let state = client.get_home_state().await?;
+
+        self.current = state.presence_locked.then_some(state.presence);
+        self.pending = None;
+
+        Ok(())
+    }
+
+    /// Returns the presence to apply when the source settled on a different state
+    /// for longer than the configured delay
+    pub fn evaluate(&mut self, now: Instant) -> Option<StatePresence> {
+        let observed = match self.source.is_present(now) {
+            true => StatePresence::Home,
+            false => StatePresence::Away,
+        };
+
+        if self.current.as_ref() == Some(&observed) {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match &self.pending {
+            Some((pending, since)) if *pending == observed => *since,
+            _ => {
+                self.pending = Some((observed.clone(), now));
+                now
+            }
+        };
+
+        let delay = match observed {
+            StatePresence::Home => self.configuration.arrival_delay,
+            _ => self.configuration.minimum_away,
+        };
+
+        if now.saturating_duration_since(since) < delay {
+            return None;
+        }
+
+        self.pending = None;
+        self.current = Some(observed.clone());
+
+        Some(observed)
+    }
+
+    /// Evaluates the source and locks the home presence on transitions
+    pub async fn update(&mut self, client: &Client) -> Result<Option<StatePresence>, Error> {
+        let (current, pending) = (self.current.clone(), self.pending.clone());
+        let transition = self.evaluate(Instant::now());
+
+        if let Some(presence) = &transition {
+            if let Err(error) = client.set_presence(presence.clone()).await {
+                // Retry the same transition on the next update
+                self.current = current;
+                self.pending = pending;
+
+                return Err(error);
+            }
+        }
+
+        Ok(transition)
+    }
+
+    /// Removes the presence lock, giving control back to tado
+    pub async fn release(&mut self, client: &Client) -> Result<(), Error> {
+        client.set_presence(StatePresence::Auto).await?;
+
+        self.current = None;
+        self.pending = None;
+
+        Ok(())
+    }
+
+    /// Updates the presence on every tick. Failures are logged and the transition
+    /// is retried on the next tick, so this never returns.
+    pub async fn run(&mut self, client: &Client, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = self.update(client).await {
+                log::warn!("Failed to update the home presence: {}", error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(bool);
+
+    impl PresenceSource for Fixed {
+        fn is_present(&mut self, _now: Instant) -> bool {
+            self.0
+        }
+    }
+
+    fn detector(present: bool) -> PresenceDetector<Fixed> {
+        PresenceDetector::new(
+            Fixed(present),
+            PresenceConfiguration {
+                arrival_delay: Duration::from_secs(30),
+                minimum_away: Duration::from_secs(600),
+            },
+        )
+    }
+
+    #[test]
+    fn arrival_waits_for_the_delay() {
+        let start = Instant::now();
+        let mut detector = detector(true);
+
+        assert_eq!(detector.evaluate(start), None);
+        assert_eq!(detector.evaluate(start + Duration::from_secs(29)), None);
+        assert_eq!(
+            detector.evaluate(start + Duration::from_secs(30)),
+            Some(StatePresence::Home)
+        );
+        assert_eq!(detector.presence(), Some(&StatePresence::Home));
+
+        // Nothing to apply while the source agrees with the current presence
+        assert_eq!(detector.evaluate(start + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn departure_waits_for_the_minimum_away() {
+        let start = Instant::now();
+        let mut detector = detector(false);
+        detector.current = Some(StatePresence::Home);
+
+        assert_eq!(detector.evaluate(start), None);
+        assert_eq!(detector.evaluate(start + Duration::from_secs(599)), None);
+        assert_eq!(
+            detector.evaluate(start + Duration::from_secs(600)),
+            Some(StatePresence::Away)
+        );
+    }
+
+    #[test]
+    fn flapping_restarts_the_delay() {
+        let start = Instant::now();
+        let mut detector = detector(false);
+        detector.current = Some(StatePresence::Home);
+
+        assert_eq!(detector.evaluate(start), None);
+
+        // Back home before the minimum away elapsed cancels the departure
+        detector.source().0 = true;
+        assert_eq!(detector.evaluate(start + Duration::from_secs(300)), None);
+        assert_eq!(detector.pending, None);
+
+        detector.source().0 = false;
+        assert_eq!(detector.evaluate(start + Duration::from_secs(400)), None);
+        assert_eq!(detector.evaluate(start + Duration::from_secs(900)), None);
+        assert_eq!(
+            detector.evaluate(start + Duration::from_secs(1000)),
+            Some(StatePresence::Away)
+        );
+    }
+
+    #[test]
+    fn seen_devices_expire() {
+        let start = Instant::now();
+        let mut devices = SeenDevices::new(Duration::from_secs(60));
+
+        assert!(!devices.is_present(start));
+
+        devices.seen_at("phone", start);
+        assert!(devices.is_present(start + Duration::from_secs(59)));
+        assert!(!devices.is_present(start + Duration::from_secs(60)));
+    }
+}
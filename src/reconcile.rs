@@ -0,0 +1,572 @@
+//! Homes configured as code: a declarative description is compared with what the
+//! [`Client`] reads, giving a plan made only of the differences, which can then be applied.
+
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::{
+    data::{
+        AwayConfiguration, DayType, DefaultOverlay, DeviceId, ScheduleBlock, TemperatureUnit, Zone,
+        ZoneFeatures, ZoneId,
+    },
+    Client, Error,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HomeDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub away_radius_in_meters: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_unit: Option<TemperatureUnit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub christmas_mode: Option<bool>,
+    #[serde(default)]
+    pub zones: Vec<ZoneDescription>,
+    #[serde(default)]
+    pub devices: Vec<DeviceDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneDescription {
+    /// Zone to configure, matched by name when none, otherwise renamed to `name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<ZoneId>,
+    pub name: String,
+    #[serde(flatten)]
+    pub features: ZoneFeatures,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub away_configuration: Option<AwayConfiguration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_overlay: Option<DefaultOverlay>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ScheduleDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleDescription {
+    /// Active timetable: 0 for one day, 1 for three days, 2 for seven days
+    pub timetable: u32,
+    /// Blocks of the day types to replace, day types without blocks are left untouched
+    #[serde(default)]
+    pub blocks: Vec<ScheduleBlock>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceDescription {
+    pub serial_no: DeviceId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child_lock: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_offset: Option<f32>,
+}
+
+impl HomeDescription {
+    pub fn from_json(source: &str) -> Result<Self, Error> {
+        serde_json::from_str(source).map_err(|error| Error::InvalidInput(error.to_string()))
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(source: &str) -> Result<Self, Error> {
+        toml::from_str(source).map_err(|error| Error::InvalidInput(error.to_string()))
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(source: &str) -> Result<Self, Error> {
+        serde_yaml::from_str(source).map_err(|error| Error::InvalidInput(error.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Change {
+    SetAwayRadius {
+        meters: f32,
+    },
+    SetTemperatureUnit {
+        unit: TemperatureUnit,
+    },
+    SetChristmasMode {
+        enabled: bool,
+    },
+    RenameZone {
+        zone: ZoneId,
+        from: String,
+        to: String,
+    },
+    SetZoneFeatures {
+        zone: ZoneId,
+        features: ZoneFeatures,
+    },
+    SetAwayConfiguration {
+        zone: ZoneId,
+        configuration: AwayConfiguration,
+    },
+    SetDefaultOverlay {
+        zone: ZoneId,
+        overlay: DefaultOverlay,
+    },
+    SetTimetable {
+        zone: ZoneId,
+        timetable: u32,
+    },
+    SetScheduleBlocks {
+        zone: ZoneId,
+        timetable: u32,
+        day_type: DayType,
+        blocks: Vec<ScheduleBlock>,
+    },
+    SetChildLock {
+        device: DeviceId,
+        enabled: bool,
+    },
+    SetTemperatureOffset {
+        device: DeviceId,
+        celsius: f32,
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::SetAwayRadius { meters } => write!(f, "home: set away radius to {}m", meters),
+            Change::SetTemperatureUnit { unit } => {
+                write!(f, "home: set temperature unit to {}", unit)
+            }
+            Change::SetChristmasMode { enabled } => {
+                write!(f, "home: set christmas mode to {}", enabled)
+            }
+            Change::RenameZone { zone, from, to } => {
+                write!(f, "zone {}: rename \"{}\" to \"{}\"", zone, from, to)
+            }
+            Change::SetZoneFeatures { zone, features } => write!(
+                f,
+                "zone {}: set features {}",
+                zone,
+                serde_json::to_string(features).unwrap_or_default()
+            ),
+            Change::SetAwayConfiguration {
+                zone,
+                configuration,
+            } => write!(
+                f,
+                "zone {}: set away configuration {}",
+                zone,
+                serde_json::to_string(configuration).unwrap_or_default()
+            ),
+            Change::SetDefaultOverlay { zone, overlay } => write!(
+                f,
+                "zone {}: set default overlay {}",
+                zone,
+                serde_json::to_string(overlay).unwrap_or_default()
+            ),
+            Change::SetTimetable { zone, timetable } => {
+                write!(f, "zone {}: activate timetable {}", zone, timetable)
+            }
+            Change::SetScheduleBlocks {
+                zone,
+                timetable,
+                day_type,
+                blocks,
+            } => write!(
+                f,
+                "zone {}: replace {} blocks of timetable {} with {} blocks",
+                zone,
+                day_type,
+                timetable,
+                blocks.len()
+            ),
+            Change::SetChildLock { device, enabled } => {
+                write!(f, "device {}: set child lock to {}", device, enabled)
+            }
+            Change::SetTemperatureOffset { device, celsius } => {
+                write!(
+                    f,
+                    "device {}: set temperature offset to {}°C",
+                    device, celsius
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "No changes");
+        }
+
+        for change in &self.changes {
+            writeln!(f, "~ {}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Whether every value of the desired document is found in the current one,
+// numbers being compared with a tolerance as they go through floats
+fn matches(desired: &JsonValue, current: &JsonValue) -> bool {
+    match (desired, current) {
+        (JsonValue::Object(desired), JsonValue::Object(current)) => {
+            desired.iter().all(|(key, value)| {
+                value.is_null() || current.get(key).is_some_and(|other| matches(value, other))
+            })
+        }
+        (JsonValue::Array(desired), JsonValue::Array(current)) => {
+            desired.len() == current.len()
+                && desired.iter().zip(current).all(|(a, b)| matches(a, b))
+        }
+        (JsonValue::Number(desired), JsonValue::Number(current)) => {
+            match (desired.as_f64(), current.as_f64()) {
+                (Some(desired), Some(current)) => (desired - current).abs() < 0.01,
+                _ => false,
+            }
+        }
+        _ => desired == current,
+    }
+}
+
+fn differs(desired: &impl Serialize, current: &impl Serialize) -> bool {
+    match (serde_json::to_value(desired), serde_json::to_value(current)) {
+        (Ok(desired), Ok(current)) => !matches(&desired, &current),
+        _ => true,
+    }
+}
+
+fn find_zone<'a>(zones: &'a [Zone], description: &ZoneDescription) -> Result<&'a Zone, Error> {
+    zones
+        .iter()
+        .find(|zone| match description.id {
+            Some(id) => zone.id == id,
+            None => zone.name == description.name,
+        })
+        .ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "Zone {} does not exist",
+                description
+                    .id
+                    .map_or_else(|| description.name.clone(), |id| id.to_string())
+            ))
+        })
+}
+
+impl Client {
+    /// Compares the description with the home, without changing anything
+    pub async fn plan(&self, description: &HomeDescription) -> Result<Plan, Error> {
+        let zones = self.get_zones().await?;
+        let mut changes = Vec::new();
+
+        if description.away_radius_in_meters.is_some()
+            || description.temperature_unit.is_some()
+            || description.christmas_mode.is_some()
+        {
+            let home = self.get_home().await?;
+
+            if let Some(meters) = description.away_radius_in_meters {
+                if (home.away_radius_in_meters - meters).abs() >= 0.01 {
+                    changes.push(Change::SetAwayRadius { meters });
+                }
+            }
+
+            if let Some(unit) = &description.temperature_unit {
+                if home.temperature_unit != *unit {
+                    changes.push(Change::SetTemperatureUnit { unit: unit.clone() });
+                }
+            }
+
+            if let Some(enabled) = description.christmas_mode {
+                if home.christmas_mode_enabled != enabled {
+                    changes.push(Change::SetChristmasMode { enabled });
+                }
+            }
+        }
+
+        for zone_description in &description.zones {
+            let zone = find_zone(&zones, zone_description)?;
+
+            if zone.name != zone_description.name {
+                changes.push(Change::RenameZone {
+                    zone: zone.id,
+                    from: zone.name.clone(),
+                    to: zone_description.name.clone(),
+                });
+            }
+
+            let desired = &zone_description.features;
+            let current = self.get_zone_features(&zone.id).await?;
+            let features = ZoneFeatures {
+                dazzle: desired
+                    .dazzle
+                    .filter(|value| current.dazzle != Some(*value)),
+                open_window_detection: desired
+                    .open_window_detection
+                    .filter(|value| current.open_window_detection != Some(*value)),
+                open_window_detection_timeout_in_seconds: desired
+                    .open_window_detection_timeout_in_seconds
+                    .filter(|value| {
+                        current.open_window_detection_timeout_in_seconds != Some(*value)
+                    }),
+                early_start: desired
+                    .early_start
+                    .filter(|value| current.early_start != Some(*value)),
+            };
+
+            if features.dazzle.is_some()
+                || features.open_window_detection.is_some()
+                || features.open_window_detection_timeout_in_seconds.is_some()
+                || features.early_start.is_some()
+            {
+                changes.push(Change::SetZoneFeatures {
+                    zone: zone.id,
+                    features,
+                });
+            }
+
+            if let Some(configuration) = &zone_description.away_configuration {
+                let current = self.get_away_configuration(&zone.id).await?;
+
+                if differs(configuration, &current) {
+                    changes.push(Change::SetAwayConfiguration {
+                        zone: zone.id,
+                        configuration: configuration.clone(),
+                    });
+                }
+            }
+
+            if let Some(overlay) = &zone_description.default_overlay {
+                let current = self.get_default_overlay(&zone.id).await?;
+
+                if differs(overlay, &current) {
+                    changes.push(Change::SetDefaultOverlay {
+                        zone: zone.id,
+                        overlay: overlay.clone(),
+                    });
+                }
+            }
+
+            if let Some(schedule) = &zone_description.schedule {
+                if self.get_schedule(&zone.id).await?.id != schedule.timetable {
+                    changes.push(Change::SetTimetable {
+                        zone: zone.id,
+                        timetable: schedule.timetable,
+                    });
+                }
+
+                let current = self
+                    .get_schedule_blocks(&zone.id, schedule.timetable)
+                    .await?;
+                let mut day_types: Vec<&DayType> = Vec::new();
+
+                for block in &schedule.blocks {
+                    if !day_types.contains(&&block.day_type) {
+                        day_types.push(&block.day_type);
+                    }
+                }
+
+                for day_type in day_types {
+                    let desired: Vec<&ScheduleBlock> = schedule
+                        .blocks
+                        .iter()
+                        .filter(|block| block.day_type == *day_type)
+                        .collect();
+                    let existing: Vec<&ScheduleBlock> = current
+                        .iter()
+                        .filter(|block| block.day_type == *day_type)
+                        .collect();
+
+                    if differs(&desired, &existing) {
+                        changes.push(Change::SetScheduleBlocks {
+                            zone: zone.id,
+                            timetable: schedule.timetable,
+                            day_type: day_type.clone(),
+                            blocks: desired.into_iter().cloned().collect(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let devices = self.get_devices().await?;
+
+        for device_description in &description.devices {
+            let device = devices
+                .iter()
+                .find(|device| device.serial_no == device_description.serial_no)
+                .ok_or_else(|| {
+                    Error::InvalidInput(format!(
+                        "Device {} does not exist",
+                        device_description.serial_no
+                    ))
+                })?;
+
+            if let Some(enabled) = device_description.child_lock {
+                if device.child_lock_enabled != Some(enabled) {
+                    changes.push(Change::SetChildLock {
+                        device: device.serial_no.clone(),
+                        enabled,
+                    });
+                }
+            }
+
+            if let Some(celsius) = device_description.temperature_offset {
+                let current = self
+                    .get_temperature_offset(device.serial_no.clone())
+                    .await?;
+
+                if (current.celsius - celsius).abs() >= 0.01 {
+                    changes.push(Change::SetTemperatureOffset {
+                        device: device.serial_no.clone(),
+                        celsius,
+                    });
+                }
+            }
+        }
+
+        Ok(Plan { changes })
+    }
+
+    /// Applies the changes of a plan in order, stopping at the first failure
+    pub async fn apply(&self, plan: &Plan) -> Result<(), Error> {
+        for change in &plan.changes {
+            match change {
+                Change::SetAwayRadius { meters } => {
+                    self.set_away_radius(*meters).await?;
+                }
+                Change::SetTemperatureUnit { unit } => {
+                    self.set_temperature_unit(unit.clone()).await?;
+                }
+                Change::SetChristmasMode { enabled } => {
+                    self.set_christmas_mode(*enabled).await?;
+                }
+                Change::RenameZone { zone, to, .. } => {
+                    self.set_zone_name(zone, to.clone()).await?;
+                }
+                Change::SetZoneFeatures { zone, features } => {
+                    self.apply_zone_features(zone, features).await?;
+                }
+                Change::SetAwayConfiguration {
+                    zone,
+                    configuration,
+                } => self.set_away_configuration(zone, configuration).await?,
+                Change::SetDefaultOverlay { zone, overlay } => {
+                    self.set_default_overlay(zone, overlay).await?;
+                }
+                Change::SetTimetable { zone, timetable } => {
+                    self.set_schedule(zone, *timetable).await?;
+                }
+                Change::SetScheduleBlocks {
+                    zone,
+                    timetable,
+                    day_type,
+                    blocks,
+                } => {
+                    self.set_schedule_blocks(zone, *timetable, day_type, blocks)
+                        .await?;
+                }
+                Change::SetChildLock { device, enabled } => {
+                    self.set_child_lock(device, *enabled).await?
+                }
+                Change::SetTemperatureOffset { device, celsius } => {
+                    self.set_temperature_offset(device, *celsius).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plans the differences with the description and applies them unless in dry run
+    pub async fn reconcile(
+        &self,
+        description: &HomeDescription,
+        dry_run: bool,
+    ) -> Result<Plan, Error> {
+        let plan = self.plan(description).await?;
+
+        if !dry_run {
+            self.apply(&plan).await?;
+        }
+
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::data::Temperature;
+
+    #[test]
+    fn matches_a_subset_of_the_current_values() {
+        let current = json!({ "enabled": true, "setting": { "power": "ON", "type": "HEATING" } });
+
+        assert!(matches(&json!({ "enabled": true }), &current));
+        assert!(matches(&json!({ "setting": { "power": "ON" } }), &current));
+        assert!(matches(&json!({ "enabled": null }), &current));
+        assert!(!matches(
+            &json!({ "setting": { "power": "OFF" } }),
+            &current
+        ));
+        assert!(!matches(&json!({ "missing": 1 }), &current));
+    }
+
+    #[test]
+    fn compares_numbers_with_a_tolerance() {
+        assert!(matches(&json!(21.5), &json!(21.504)));
+        assert!(matches(&json!(21), &json!(21.0)));
+        assert!(!matches(&json!(21.5), &json!(21.6)));
+        assert!(!matches(&json!(21.5), &json!("21.5")));
+    }
+
+    #[test]
+    fn arrays_must_have_the_same_length() {
+        assert!(matches(&json!([1, 2]), &json!([1, 2])));
+        assert!(!matches(&json!([1]), &json!([1, 2])));
+        assert!(!matches(&json!([1, 2]), &json!([1])));
+        assert!(!matches(&json!([1, 3]), &json!([1, 2])));
+    }
+
+    #[test]
+    fn celsius_only_descriptions_differ_from_the_home_only_by_their_values() {
+        let description = HomeDescription::from_json(
+            r#"{
+                "zones": [{
+                    "name": "Living room",
+                    "awayConfiguration": {
+                        "type": "HEATING",
+                        "autoAdjust": false,
+                        "comfortLevel": 50,
+                        "minimumAwayTemperature": { "celsius": 16 }
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+        let desired = description.zones[0].away_configuration.as_ref().unwrap();
+        let mut current = desired.clone();
+
+        assert!(!differs(desired, &current));
+
+        current.minimum_away_temperature = Some(Temperature::from_celsius(17.0));
+        assert!(differs(desired, &current));
+    }
+}
@@ -0,0 +1,258 @@
+//! Generation agnostic rooms, so the same automation drives homes made of
+//! V3+ zones and homes made of tado X rooms.
+
+use std::{future::Future, time::Duration};
+
+use crate::{
+    data::{
+        timer_seconds, Overlay, OverlayTerminationCondition, Power, Temperature, ZoneId,
+        ZoneSetting, ZoneType,
+    },
+    x::{RoomSetting, RoomState, RoomTermination, TadoX, TemperatureValue},
+    Client, Error,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomStatus {
+    /// Zone id for V3+ homes, room id for tado X homes
+    pub id: u32,
+    pub name: String,
+    pub temperature: Option<f32>,
+    pub humidity: Option<f32>,
+    /// Temperature the room is heated to, none when it is turned off
+    pub target: Option<f32>,
+    pub manual: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Termination {
+    Manual,
+    NextTimeBlock,
+    Timer(Duration),
+}
+
+pub trait Rooms {
+    fn list_rooms(&self) -> impl Future<Output = Result<Vec<RoomStatus>, Error>> + Send;
+
+    fn get_room_status(&self, room: u32) -> impl Future<Output = Result<RoomStatus, Error>> + Send;
+
+    /// Sets the target temperature of the room, or turns it off when none
+    fn set_room_target(
+        &self,
+        room: u32,
+        target: Option<f32>,
+        termination: Termination,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn resume_room_schedule(&self, room: u32) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+impl Client {
+    async fn get_room_status_of(&self, zone: &ZoneId, name: String) -> Result<RoomStatus, Error> {
+        let state = self.get_state(zone).await?;
+        let sensors = state.sensor_data_points.as_ref();
+
+        Ok(RoomStatus {
+            id: *zone,
+            name,
+            temperature: sensors
+                .and_then(|sensors| sensors.inside_temperature.as_ref())
+                .map(|temperature| temperature.celsius),
+            humidity: sensors
+                .and_then(|sensors| sensors.humidity.as_ref())
+                .map(|humidity| humidity.percentage),
+            target: state
+                .setting
+                .as_ref()
+                .filter(|setting| setting.power == Power::On)
+                .and_then(|setting| setting.temperature.as_ref())
+                .map(|temperature| temperature.celsius),
+            manual: state.overlay.is_some(),
+        })
+    }
+}
+
+impl Rooms for Client {
+    async fn list_rooms(&self) -> Result<Vec<RoomStatus>, Error> {
+        let mut rooms = Vec::new();
+
+        // Hot water zones don't represent a room
+        for zone in self.get_zones().await? {
+            if zone.r#type != ZoneType::HotWater {
+                rooms.push(self.get_room_status_of(&zone.id, zone.name).await?);
+            }
+        }
+
+        Ok(rooms)
+    }
+
+    async fn get_room_status(&self, room: u32) -> Result<RoomStatus, Error> {
+        let zone = self.get_zone(&room).await?;
+
+        self.get_room_status_of(&zone.id, zone.name).await
+    }
+
+    async fn set_room_target(
+        &self,
+        room: u32,
+        target: Option<f32>,
+        termination: Termination,
+    ) -> Result<(), Error> {
+        let zone = self.get_zone(&room).await?;
+
+        // Start from the current setting to keep air conditioning modes and fan levels
+        let mut setting = self
+            .get_state(&room)
+            .await?
+            .setting
+            .filter(|setting| setting.r#type == zone.r#type)
+            .unwrap_or_else(|| ZoneSetting::new(zone.r#type.clone(), Power::Off, None));
+
+        setting.power = match target {
+            Some(_) => Power::On,
+            None => Power::Off,
+        };
+        setting.temperature = target.map(Temperature::from_celsius);
+
+        let termination = match termination {
+            Termination::Manual => OverlayTerminationCondition::Manual,
+            Termination::NextTimeBlock => OverlayTerminationCondition::TadoMode,
+            Termination::Timer(duration) => OverlayTerminationCondition::timer(duration)?,
+        };
+
+        self.set_overlay(
+            &room,
+            &Overlay {
+                setting,
+                termination,
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn resume_room_schedule(&self, room: u32) -> Result<(), Error> {
+        self.end_manual_control(&room).await
+    }
+}
+
+impl Rooms for TadoX<'_> {
+    async fn list_rooms(&self) -> Result<Vec<RoomStatus>, Error> {
+        Ok(self
+            .get_rooms()
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    async fn get_room_status(&self, room: u32) -> Result<RoomStatus, Error> {
+        Ok(self.get_room(&room).await?.into())
+    }
+
+    async fn set_room_target(
+        &self,
+        room: u32,
+        target: Option<f32>,
+        termination: Termination,
+    ) -> Result<(), Error> {
+        let setting = RoomSetting {
+            power: match target {
+                Some(_) => Power::On,
+                None => Power::Off,
+            },
+            temperature: target.map(|value| TemperatureValue { value }),
+        };
+
+        let termination = match termination {
+            Termination::Manual => RoomTermination::Manual,
+            Termination::NextTimeBlock => RoomTermination::NextTimeBlock,
+            Termination::Timer(duration) => RoomTermination::Timer {
+                duration_in_seconds: timer_seconds(duration)?,
+            },
+        };
+
+        self.set_manual_control(&room, &setting, &termination).await
+    }
+
+    async fn resume_room_schedule(&self, room: u32) -> Result<(), Error> {
+        self.resume_schedule(&room).await
+    }
+}
+
+impl From<RoomState> for RoomStatus {
+    fn from(room: RoomState) -> Self {
+        Self {
+            id: room.id,
+            name: room.name,
+            temperature: room
+                .sensor_data_points
+                .inside_temperature
+                .map(|temperature| temperature.value),
+            humidity: room
+                .sensor_data_points
+                .humidity
+                .map(|humidity| humidity.percentage),
+            target: match room.setting.power {
+                Power::On => room
+                    .setting
+                    .temperature
+                    .map(|temperature| temperature.value),
+                Power::Off => None,
+            },
+            manual: room.manual_control_termination.is_some(),
+        }
+    }
+}
+
+/// Rooms of a home, whatever its generation
+pub enum AnyRooms<'a> {
+    Zones(&'a Client),
+    TadoX(TadoX<'a>),
+}
+
+impl Client {
+    /// Picks the API matching the generation of the home
+    pub async fn rooms(&self) -> Result<AnyRooms<'_>, Error> {
+        Ok(match self.get_home().await?.is_tado_x() {
+            true => AnyRooms::TadoX(self.tado_x()),
+            false => AnyRooms::Zones(self),
+        })
+    }
+}
+
+impl Rooms for AnyRooms<'_> {
+    async fn list_rooms(&self) -> Result<Vec<RoomStatus>, Error> {
+        match self {
+            AnyRooms::Zones(client) => client.list_rooms().await,
+            AnyRooms::TadoX(x) => x.list_rooms().await,
+        }
+    }
+
+    async fn get_room_status(&self, room: u32) -> Result<RoomStatus, Error> {
+        match self {
+            AnyRooms::Zones(client) => client.get_room_status(room).await,
+            AnyRooms::TadoX(x) => x.get_room_status(room).await,
+        }
+    }
+
+    async fn set_room_target(
+        &self,
+        room: u32,
+        target: Option<f32>,
+        termination: Termination,
+    ) -> Result<(), Error> {
+        match self {
+            AnyRooms::Zones(client) => client.set_room_target(room, target, termination).await,
+            AnyRooms::TadoX(x) => x.set_room_target(room, target, termination).await,
+        }
+    }
+
+    async fn resume_room_schedule(&self, room: u32) -> Result<(), Error> {
+        match self {
+            AnyRooms::Zones(client) => client.resume_room_schedule(room).await,
+            AnyRooms::TadoX(x) => x.resume_room_schedule(room).await,
+        }
+    }
+}
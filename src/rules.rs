@@ -0,0 +1,666 @@
+//! Embeddable rules engine: declarative conditions over zone states, weather and presence
+//! trigger [`Client`] actions. Rules can be loaded from TOML or YAML with the `toml` and
+//! `yaml` features, e.g.
+//!
+//! ```toml
+//! [[rules]]
+//! name = "Window open"
+//! when = { type = "window_open", zone = 1 }
+//! for_minutes = 10
+//! then = [{ type = "turn_zone_off", zone = 1 }]
+//! ```
+//!
+//! Presences of `set_presence` actions are written `home`, `away` or `auto`, the
+//! `HOME`, `AWAY` and `AUTO` spellings of the API are accepted as well.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+
+use crate::{
+    data::{MobileDevice, StatePresence, Weather, ZoneId, ZoneState},
+    room::{Rooms, Termination},
+    Client, Error,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    WindowOpen {
+        zone: ZoneId,
+    },
+    ZoneTemperatureAbove {
+        zone: ZoneId,
+        celsius: f32,
+    },
+    ZoneTemperatureBelow {
+        zone: ZoneId,
+        celsius: f32,
+    },
+    OutsideTemperatureAbove {
+        celsius: f32,
+    },
+    OutsideTemperatureBelow {
+        celsius: f32,
+    },
+    /// Every geo tracked mobile device is known to be away from home
+    EveryoneAway,
+    AnyoneHome,
+    All {
+        conditions: Vec<Condition>,
+    },
+    Any {
+        conditions: Vec<Condition>,
+    },
+    Not {
+        condition: Box<Condition>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    TurnZoneOff {
+        zone: ZoneId,
+    },
+    SetZoneTemperature {
+        zone: ZoneId,
+        celsius: f32,
+        /// Until the next schedule block when none
+        minutes: Option<u64>,
+    },
+    ResumeSchedule {
+        zone: ZoneId,
+    },
+    SetPresence {
+        #[serde(deserialize_with = "deserialize_presence")]
+        presence: StatePresence,
+    },
+}
+
+fn deserialize_presence<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<StatePresence, D::Error> {
+    let presence = String::deserialize(deserializer)?;
+
+    match presence.to_ascii_lowercase().as_str() {
+        "home" => Ok(StatePresence::Home),
+        "away" => Ok(StatePresence::Away),
+        "auto" => Ok(StatePresence::Auto),
+        _ => Err(D::Error::unknown_variant(
+            &presence,
+            &["home", "away", "auto"],
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub when: Condition,
+    /// How long the condition must hold before the rule fires
+    #[serde(default)]
+    pub for_minutes: u64,
+    pub then: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    #[cfg(feature = "toml")]
+    pub fn from_toml(source: &str) -> Result<Self, Error> {
+        toml::from_str(source).map_err(|error| Error::InvalidInput(error.to_string()))
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(source: &str) -> Result<Self, Error> {
+        serde_yaml::from_str(source).map_err(|error| Error::InvalidInput(error.to_string()))
+    }
+}
+
+/// What the conditions are evaluated against, gathered once per evaluation
+#[derive(Debug, Clone, Default)]
+pub struct Facts {
+    pub zones: HashMap<ZoneId, ZoneState>,
+    pub weather: Option<Weather>,
+    pub mobile_devices: Vec<MobileDevice>,
+}
+
+impl Facts {
+    fn inside_temperature(&self, zone: &ZoneId) -> Option<f32> {
+        self.zones
+            .get(zone)?
+            .sensor_data_points
+            .as_ref()?
+            .inside_temperature
+            .as_ref()
+            .map(|temperature| temperature.celsius)
+    }
+
+    fn tracked_devices(&self) -> impl Iterator<Item = &MobileDevice> {
+        self.mobile_devices
+            .iter()
+            .filter(|device| device.settings.geo_tracking_enabled)
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, facts: &Facts) -> bool {
+        let outside = facts
+            .weather
+            .as_ref()
+            .map(|weather| weather.outside_temperature.celsius);
+
+        match self {
+            Condition::WindowOpen { zone } => facts
+                .zones
+                .get(zone)
+                .and_then(|state| state.open_window_detected)
+                .unwrap_or(false),
+            Condition::ZoneTemperatureAbove { zone, celsius } => facts
+                .inside_temperature(zone)
+                .is_some_and(|temperature| temperature > *celsius),
+            Condition::ZoneTemperatureBelow { zone, celsius } => facts
+                .inside_temperature(zone)
+                .is_some_and(|temperature| temperature < *celsius),
+            Condition::OutsideTemperatureAbove { celsius } => {
+                outside.is_some_and(|temperature| temperature > *celsius)
+            }
+            Condition::OutsideTemperatureBelow { celsius } => {
+                outside.is_some_and(|temperature| temperature < *celsius)
+            }
+            Condition::EveryoneAway => {
+                facts.tracked_devices().next().is_some()
+                    && facts.tracked_devices().all(|device| {
+                        device
+                            .location
+                            .as_ref()
+                            .is_some_and(|location| !location.stale && !location.at_home)
+                    })
+            }
+            Condition::AnyoneHome => facts.tracked_devices().any(|device| {
+                device
+                    .location
+                    .as_ref()
+                    .is_some_and(|location| location.at_home)
+            }),
+            Condition::All { conditions } => conditions.iter().all(|c| c.holds(facts)),
+            Condition::Any { conditions } => conditions.iter().any(|c| c.holds(facts)),
+            Condition::Not { condition } => !condition.holds(facts),
+        }
+    }
+
+    fn collect(&self, zones: &mut HashSet<ZoneId>, weather: &mut bool, presence: &mut bool) {
+        match self {
+            Condition::WindowOpen { zone }
+            | Condition::ZoneTemperatureAbove { zone, .. }
+            | Condition::ZoneTemperatureBelow { zone, .. } => {
+                zones.insert(*zone);
+            }
+            Condition::OutsideTemperatureAbove { .. }
+            | Condition::OutsideTemperatureBelow { .. } => *weather = true,
+            Condition::EveryoneAway | Condition::AnyoneHome => *presence = true,
+            Condition::All { conditions } | Condition::Any { conditions } => conditions
+                .iter()
+                .for_each(|condition| condition.collect(zones, weather, presence)),
+            Condition::Not { condition } => condition.collect(zones, weather, presence),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Firing {
+    pub rule: String,
+    pub at: SystemTime,
+    pub actions: Vec<Action>,
+    /// Actions were only logged, not executed
+    pub dry_run: bool,
+    /// Errors of the actions that failed, the others were still executed
+    pub errors: Vec<String>,
+}
+
+/// Firings kept in the log of the engine, the oldest are dropped first
+pub const LOG_CAPACITY: usize = 256;
+
+struct RuleState {
+    since: Option<Instant>,
+    fired: bool,
+}
+
+pub struct Engine {
+    rules: Vec<Rule>,
+    states: Vec<RuleState>,
+    dry_run: bool,
+    log: VecDeque<Firing>,
+}
+
+impl Engine {
+    pub fn new(rules: RuleSet, dry_run: bool) -> Self {
+        Self {
+            states: rules
+                .rules
+                .iter()
+                .map(|_| RuleState {
+                    since: None,
+                    fired: false,
+                })
+                .collect(),
+            rules: rules.rules,
+            dry_run,
+            log: VecDeque::with_capacity(LOG_CAPACITY),
+        }
+    }
+
+    /// Last firings, up to [`LOG_CAPACITY`], from the oldest to the newest
+    pub fn log(&self) -> impl Iterator<Item = &Firing> {
+        self.log.iter()
+    }
+
+    /// Takes the firings out of the log
+    pub fn drain_log(&mut self) -> Vec<Firing> {
+        self.log.drain(..).collect()
+    }
+
+    fn record(&mut self, firing: Firing) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+
+        self.log.push_back(firing);
+    }
+
+    /// Reads only the data the conditions need
+    pub async fn gather(&self, client: &Client) -> Result<Facts, Error> {
+        let (mut zones, mut weather, mut presence) = (HashSet::new(), false, false);
+
+        for rule in &self.rules {
+            rule.when.collect(&mut zones, &mut weather, &mut presence);
+        }
+
+        let mut facts = Facts::default();
+
+        for zone in zones {
+            facts.zones.insert(zone, client.get_state(&zone).await?);
+        }
+
+        if weather {
+            facts.weather = Some(client.get_weather().await?);
+        }
+
+        if presence {
+            facts.mobile_devices = client.get_mobile_devices().await?;
+        }
+
+        Ok(facts)
+    }
+
+    /// Returns the rules whose condition held long enough, once per activation
+    pub fn triggered(&mut self, facts: &Facts, now: Instant) -> Vec<usize> {
+        let mut triggered = Vec::new();
+
+        for (index, (rule, state)) in self.rules.iter().zip(self.states.iter_mut()).enumerate() {
+            if !rule.when.holds(facts) {
+                state.since = None;
+                state.fired = false;
+                continue;
+            }
+
+            let since = *state.since.get_or_insert(now);
+            let delay = Duration::from_secs(rule.for_minutes * 60);
+
+            if !state.fired && now.saturating_duration_since(since) >= delay {
+                state.fired = true;
+                triggered.push(index);
+            }
+        }
+
+        triggered
+    }
+
+    pub async fn evaluate(&mut self, client: &Client) -> Result<Vec<Firing>, Error> {
+        let facts = self.gather(client).await?;
+        let mut firings = Vec::new();
+
+        for index in self.triggered(&facts, Instant::now()) {
+            let rule = &self.rules[index];
+            let mut errors = Vec::new();
+
+            if !self.dry_run {
+                for action in &rule.then {
+                    if let Err(error) = execute(client, action).await {
+                        errors.push(error.to_string());
+                    }
+                }
+            }
+
+            firings.push(Firing {
+                rule: rule.name.clone(),
+                at: SystemTime::now(),
+                actions: rule.then.clone(),
+                dry_run: self.dry_run,
+                errors,
+            });
+        }
+
+        for firing in &firings {
+            self.record(firing.clone());
+        }
+
+        Ok(firings)
+    }
+
+    /// Evaluates the rules on every tick. Failures are logged and the rules are
+    /// evaluated again on the next tick, so this never returns.
+    pub async fn run(&mut self, client: &Client, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = self.evaluate(client).await {
+                log::warn!("Failed to evaluate the rules: {}", error);
+            }
+        }
+    }
+}
+
+async fn execute(client: &Client, action: &Action) -> Result<(), Error> {
+    match action {
+        Action::TurnZoneOff { zone } => {
+            client
+                .set_room_target(*zone, None, Termination::NextTimeBlock)
+                .await
+        }
+        Action::SetZoneTemperature {
+            zone,
+            celsius,
+            minutes,
+        } => {
+            let termination = match minutes {
+                Some(minutes) => Termination::Timer(Duration::from_secs(minutes * 60)),
+                None => Termination::NextTimeBlock,
+            };
+
+            client
+                .set_room_target(*zone, Some(*celsius), termination)
+                .await
+        }
+        Action::ResumeSchedule { zone } => client.resume_room_schedule(*zone).await,
+        Action::SetPresence { presence } => client.set_presence(presence.clone()).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn zone(open_window: bool, celsius: f32) -> ZoneState {
+        serde_json::from_value(json!({
+            "openWindowDetected": open_window,
+            "sensorDataPoints": {
+                "insideTemperature": {
+                    "celsius": celsius,
+                    "fahrenheit": celsius * 9.0 / 5.0 + 32.0,
+                    "timestamp": "2024-03-01T10:15:30.000Z",
+                },
+            },
+        }))
+        .unwrap()
+    }
+
+    fn mobile_device(tracked: bool, at_home: bool) -> MobileDevice {
+        serde_json::from_value(json!({
+            "id": 1,
+            "name": "Phone",
+            "settings": {
+                "geoTrackingEnabled": tracked,
+                "onDemandLogRetrievalEnabled": false,
+                "pushNotifications": {
+                    "lowBatteryReminder": false,
+                    "awayModeReminder": false,
+                    "homeModeReminder": false,
+                    "openWindowReminder": false,
+                    "energySavingsReportReminder": false,
+                    "incidentDetection": false,
+                },
+            },
+            "location": {
+                "stale": false,
+                "atHome": at_home,
+                "bearingFromHome": { "degrees": 0.0, "radians": 0.0 },
+                "relativeDistanceFromHomeFence": 0.0,
+            },
+            "deviceMetadata": {
+                "platform": "Android",
+                "osVersion": "14",
+                "model": "Pixel",
+                "locale": "en",
+            },
+        }))
+        .unwrap()
+    }
+
+    fn rule(when: Condition, for_minutes: u64) -> Rule {
+        Rule {
+            name: "Rule".to_string(),
+            when,
+            for_minutes,
+            then: vec![Action::TurnZoneOff { zone: 1 }],
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn loads_toml() {
+        let rules = RuleSet::from_toml(
+            r#"
+            [[rules]]
+            name = "Window open"
+            when = { type = "window_open", zone = 1 }
+            for_minutes = 10
+            then = [{ type = "turn_zone_off", zone = 1 }]
+
+            [[rules]]
+            name = "Nobody home"
+            when = { type = "all", conditions = [{ type = "everyone_away" }, { type = "not", condition = { type = "window_open", zone = 2 } }] }
+            then = [{ type = "set_presence", presence = "away" }, { type = "set_presence", presence = "HOME" }]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(rules.rules.len(), 2);
+        assert_eq!(rules.rules[0].for_minutes, 10);
+        assert!(matches!(
+            rules.rules[0].when,
+            Condition::WindowOpen { zone: 1 }
+        ));
+        assert_eq!(rules.rules[1].for_minutes, 0);
+        assert!(matches!(
+            &rules.rules[1].then[..],
+            [
+                Action::SetPresence {
+                    presence: StatePresence::Away
+                },
+                Action::SetPresence {
+                    presence: StatePresence::Home
+                }
+            ]
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn rejects_unknown_presences() {
+        assert!(RuleSet::from_toml(
+            r#"
+            [[rules]]
+            name = "Gone"
+            when = { type = "everyone_away" }
+            then = [{ type = "set_presence", presence = "elsewhere" }]
+            "#,
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn loads_yaml() {
+        let rules = RuleSet::from_yaml(
+            r#"
+            rules:
+              - name: Too warm
+                when:
+                  type: zone_temperature_above
+                  zone: 3
+                  celsius: 24.5
+                then:
+                  - type: set_zone_temperature
+                    zone: 3
+                    celsius: 20
+                    minutes: 30
+            "#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            rules.rules[0].then[..],
+            [Action::SetZoneTemperature {
+                zone: 3,
+                minutes: Some(30),
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn evaluates_conditions() {
+        let mut facts = Facts::default();
+        facts.zones.insert(1, zone(true, 19.0));
+        facts.zones.insert(2, zone(false, 23.0));
+
+        assert!(Condition::WindowOpen { zone: 1 }.holds(&facts));
+        assert!(!Condition::WindowOpen { zone: 2 }.holds(&facts));
+        assert!(!Condition::WindowOpen { zone: 3 }.holds(&facts));
+        assert!(Condition::ZoneTemperatureAbove {
+            zone: 2,
+            celsius: 22.0
+        }
+        .holds(&facts));
+        assert!(!Condition::ZoneTemperatureBelow {
+            zone: 2,
+            celsius: 22.0
+        }
+        .holds(&facts));
+        // Without weather, outside temperatures never hold
+        assert!(!Condition::OutsideTemperatureBelow { celsius: 100.0 }.holds(&facts));
+
+        let window = || Condition::WindowOpen { zone: 1 };
+        let warm = || Condition::ZoneTemperatureAbove {
+            zone: 1,
+            celsius: 22.0,
+        };
+
+        assert!(!Condition::All {
+            conditions: vec![window(), warm()]
+        }
+        .holds(&facts));
+        assert!(Condition::Any {
+            conditions: vec![window(), warm()]
+        }
+        .holds(&facts));
+        assert!(Condition::Not {
+            condition: Box::new(warm())
+        }
+        .holds(&facts));
+    }
+
+    #[test]
+    fn evaluates_presence() {
+        let mut facts = Facts::default();
+
+        // Nobody tracked doesn't mean everyone left
+        assert!(!Condition::EveryoneAway.holds(&facts));
+
+        facts.mobile_devices = vec![mobile_device(true, false), mobile_device(false, true)];
+        assert!(Condition::EveryoneAway.holds(&facts));
+        assert!(!Condition::AnyoneHome.holds(&facts));
+
+        facts.mobile_devices.push(mobile_device(true, true));
+        assert!(!Condition::EveryoneAway.holds(&facts));
+        assert!(Condition::AnyoneHome.holds(&facts));
+    }
+
+    #[test]
+    fn triggers_once_the_condition_held_long_enough() {
+        let mut engine = Engine::new(
+            RuleSet {
+                rules: vec![rule(Condition::WindowOpen { zone: 1 }, 10)],
+            },
+            true,
+        );
+        let (mut open, mut closed) = (Facts::default(), Facts::default());
+        open.zones.insert(1, zone(true, 20.0));
+        closed.zones.insert(1, zone(false, 20.0));
+
+        let start = Instant::now();
+        let minutes = |minutes: u64| start + Duration::from_secs(minutes * 60);
+
+        assert!(engine.triggered(&open, start).is_empty());
+        assert!(engine.triggered(&open, minutes(9)).is_empty());
+        assert_eq!(engine.triggered(&open, minutes(10)), vec![0]);
+
+        // Fires once per activation
+        assert!(engine.triggered(&open, minutes(20)).is_empty());
+
+        // Closing the window resets the delay
+        assert!(engine.triggered(&closed, minutes(21)).is_empty());
+        assert!(engine.triggered(&open, minutes(22)).is_empty());
+        assert!(engine.triggered(&closed, minutes(30)).is_empty());
+        assert!(engine.triggered(&open, minutes(31)).is_empty());
+        assert_eq!(engine.triggered(&open, minutes(41)), vec![0]);
+    }
+
+    #[test]
+    fn keeps_the_last_firings() {
+        let mut engine = Engine::new(RuleSet::default(), true);
+
+        for index in 0..LOG_CAPACITY + 10 {
+            engine.record(Firing {
+                rule: index.to_string(),
+                at: SystemTime::now(),
+                actions: Vec::new(),
+                dry_run: true,
+                errors: Vec::new(),
+            });
+        }
+
+        assert_eq!(engine.log().count(), LOG_CAPACITY);
+        assert_eq!(engine.log().next().unwrap().rule, "10");
+
+        let drained = engine.drain_log();
+        assert_eq!(drained.len(), LOG_CAPACITY);
+        assert_eq!(engine.log().count(), 0);
+    }
+
+    #[test]
+    fn triggers_immediately_without_delay() {
+        let mut engine = Engine::new(
+            RuleSet {
+                rules: vec![rule(Condition::WindowOpen { zone: 1 }, 0)],
+            },
+            true,
+        );
+        let mut facts = Facts::default();
+        facts.zones.insert(1, zone(true, 20.0));
+
+        assert_eq!(engine.triggered(&facts, Instant::now()), vec![0]);
+    }
+}
@@ -0,0 +1,681 @@
+//! Schedules of zones outside of the tado app: calendars (iCalendar) to look at them
+//! and a compact text format to read and edit them, one block per line:
+//!
+//! ```text
+//! Mon-Fri 06:30-08:00 21°C
+//! Mon-Fri 08:00-17:00 off
+//! Sat 00:00-00:00 19.5°C geolocation-override
+//! ```
+
+use crate::{
+    data::{
+        AcMode, DayType, Power, ScheduleBlock, Temperature, Timezone, ZoneId, ZoneSetting, ZoneType,
+    },
+    Client, Error,
+};
+
+const GEOLOCATION_OVERRIDE: &str = "geolocation-override";
+
+const WEEK: [(&str, &str); 7] = [
+    ("Mon", "MO"),
+    ("Tue", "TU"),
+    ("Wed", "WE"),
+    ("Thu", "TH"),
+    ("Fri", "FR"),
+    ("Sat", "SA"),
+    ("Sun", "SU"),
+];
+
+// Days of the week, Monday being 0, covered by the day type
+fn days(day_type: &DayType) -> std::ops::RangeInclusive<usize> {
+    match day_type {
+        DayType::MondayToSunday => 0..=6,
+        DayType::MondayToFriday => 0..=4,
+        DayType::Monday => 0..=0,
+        DayType::Tuesday => 1..=1,
+        DayType::Wednesday => 2..=2,
+        DayType::Thursday => 3..=3,
+        DayType::Friday => 4..=4,
+        DayType::Saturday => 5..=5,
+        DayType::Sunday => 6..=6,
+    }
+}
+
+fn day_label(day_type: &DayType) -> String {
+    let days = days(day_type);
+
+    match days.start() == days.end() {
+        true => WEEK[*days.start()].0.to_string(),
+        false => format!("{}-{}", WEEK[*days.start()].0, WEEK[*days.end()].0),
+    }
+}
+
+fn parse_day_type(label: &str) -> Result<DayType, Error> {
+    [
+        DayType::MondayToSunday,
+        DayType::MondayToFriday,
+        DayType::Monday,
+        DayType::Tuesday,
+        DayType::Wednesday,
+        DayType::Thursday,
+        DayType::Friday,
+        DayType::Saturday,
+        DayType::Sunday,
+    ]
+    .into_iter()
+    .find(|day_type| day_label(day_type).eq_ignore_ascii_case(label))
+    .ok_or_else(|| Error::InvalidInput(format!("Unknown days {}", label)))
+}
+
+// Minutes since midnight of a HH:MM time
+fn parse_time(time: &str) -> Result<u32, Error> {
+    let invalid = || Error::InvalidInput(format!("Invalid time {}, expected HH:MM", time));
+    let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+
+    if hours.len() != 2 || minutes.len() != 2 {
+        return Err(invalid());
+    }
+
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+
+    match hours < 24 && minutes < 60 {
+        true => Ok(hours * 60 + minutes),
+        false => Err(invalid()),
+    }
+}
+
+fn parse_mode(mode: &str) -> Option<AcMode> {
+    match mode.to_ascii_lowercase().as_str() {
+        "cool" => Some(AcMode::Cool),
+        "heat" => Some(AcMode::Heat),
+        "dry" => Some(AcMode::Dry),
+        "fan" => Some(AcMode::Fan),
+        "auto" => Some(AcMode::Auto),
+        _ => None,
+    }
+}
+
+fn setting_label(setting: &ZoneSetting) -> String {
+    let mut label = match (&setting.power, &setting.temperature) {
+        (Power::Off, _) => "off".to_string(),
+        (Power::On, Some(temperature)) => format!("{}°C", temperature.celsius),
+        (Power::On, None) => "on".to_string(),
+    };
+
+    if let Some(mode) = &setting.mode {
+        label.push(' ');
+        label.push_str(&mode.to_string().to_ascii_lowercase());
+    }
+
+    label
+}
+
+/// Renders the blocks in the text format, one line per block
+pub fn to_text(blocks: &[ScheduleBlock]) -> String {
+    blocks
+        .iter()
+        .map(|block| {
+            let mut line = format!(
+                "{} {}-{} {}",
+                day_label(&block.day_type),
+                block.start,
+                block.end,
+                setting_label(&block.setting)
+            );
+
+            if block.geolocation_override {
+                line.push(' ');
+                line.push_str(GEOLOCATION_OVERRIDE);
+            }
+
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses blocks in the text format for a zone of the given type.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse_text(text: &str, zone_type: &ZoneType) -> Result<Vec<ScheduleBlock>, Error> {
+    let mut blocks = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = |reason: String| {
+            Error::InvalidInput(format!("Line {}: {} in \"{}\"", number + 1, reason, line))
+        };
+
+        let mut tokens = line.split_whitespace();
+        let (Some(days), Some(times), Some(setting)) =
+            (tokens.next(), tokens.next(), tokens.next())
+        else {
+            return Err(invalid("expected days, times and setting".to_string()));
+        };
+
+        let day_type = parse_day_type(days).map_err(|error| invalid(error.to_string()))?;
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| invalid(format!("invalid times {}", times)))?;
+        let from = parse_time(start).map_err(|error| invalid(error.to_string()))?;
+        let to = parse_time(end).map_err(|error| invalid(error.to_string()))?;
+
+        // Blocks ending at 00:00 last until the end of the day
+        if to != 0 && to <= from {
+            return Err(invalid(format!("{} is not after {}", end, start)));
+        }
+
+        let mut setting = match setting.to_ascii_lowercase().as_str() {
+            "off" => ZoneSetting::new(zone_type.clone(), Power::Off, None),
+            "on" => ZoneSetting::new(zone_type.clone(), Power::On, None),
+            temperature => {
+                let celsius: f32 = temperature
+                    .trim_end_matches('c')
+                    .trim_end_matches('°')
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid setting {}", setting)))?;
+
+                ZoneSetting::new(
+                    zone_type.clone(),
+                    Power::On,
+                    Some(Temperature::from_celsius(celsius)),
+                )
+            }
+        };
+
+        let mut geolocation_override = false;
+
+        for token in tokens {
+            if token.eq_ignore_ascii_case(GEOLOCATION_OVERRIDE) {
+                geolocation_override = true;
+            } else if let Some(mode) = parse_mode(token) {
+                if *zone_type != ZoneType::AirConditioning {
+                    return Err(invalid(format!(
+                        "mode {} is only supported by {} zones",
+                        token,
+                        ZoneType::AirConditioning
+                    )));
+                }
+
+                setting.mode = Some(mode);
+            } else {
+                return Err(invalid(format!("unknown option {}", token)));
+            }
+        }
+
+        setting
+            .validate()
+            .map_err(|error| invalid(error.to_string()))?;
+
+        blocks.push(ScheduleBlock {
+            day_type,
+            start: start.to_string(),
+            end: end.to_string(),
+            geolocation_override,
+            setting,
+        });
+    }
+
+    Ok(blocks)
+}
+
+// Date of a day of the week, Monday being 0, as YYYYMMDD.
+// Recurrences start on the current week of the home.
+#[cfg(feature = "chrono")]
+fn week_day(timezone: &Timezone, day: usize) -> String {
+    use chrono::{Datelike, Days};
+
+    let today = chrono::Utc::now().with_timezone(timezone).date_naive();
+    let monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+
+    (monday + Days::new(day as u64))
+        .format("%Y%m%d")
+        .to_string()
+}
+
+// Date of a day of the week, Monday being 0, as YYYYMMDD.
+// Without chrono recurrences start on the week of Monday 2024-01-01.
+#[cfg(not(feature = "chrono"))]
+fn week_day(_timezone: &Timezone, day: usize) -> String {
+    format!("202401{:02}", day + 1)
+}
+
+#[cfg(feature = "chrono")]
+fn timestamp() -> String {
+    chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+#[cfg(not(feature = "chrono"))]
+fn timestamp() -> String {
+    "20240101T000000Z".to_string()
+}
+
+// Property parameter giving the timezone of local times
+#[cfg(feature = "chrono")]
+fn time_zone_id(timezone: &Timezone) -> String {
+    format!(";TZID={}", timezone)
+}
+
+// Without timezone data, times are floating: calendars show them in their own timezone
+#[cfg(not(feature = "chrono"))]
+fn time_zone_id(_timezone: &Timezone) -> String {
+    String::new()
+}
+
+#[cfg(feature = "chrono")]
+fn utc_offset(offset: chrono::FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+// Definition of the timezone referenced by the events, with the offset changes
+// of the next ten years as calendars don't know the rules of the timezone
+#[cfg(feature = "chrono")]
+fn time_zone(timezone: &Timezone) -> Vec<String> {
+    use chrono::{DateTime, Days, Offset, TimeDelta, TimeZone, Utc};
+    use chrono_tz::{OffsetComponents, OffsetName, TzOffset};
+
+    let offset_at =
+        |instant: DateTime<Utc>| timezone.offset_from_utc_datetime(&instant.naive_utc());
+    let observance = |from: &TzOffset, to: &TzOffset, at: DateTime<Utc>| {
+        let kind = match to.dst_offset().is_zero() {
+            true => "STANDARD",
+            false => "DAYLIGHT",
+        };
+        let mut lines = vec![
+            format!("BEGIN:{}", kind),
+            // Observances start at the local time before the change
+            format!(
+                "DTSTART:{}",
+                (at.naive_utc() + from.fix()).format("%Y%m%dT%H%M%S")
+            ),
+            format!("TZOFFSETFROM:{}", utc_offset(from.fix())),
+            format!("TZOFFSETTO:{}", utc_offset(to.fix())),
+        ];
+
+        if let Some(name) = to.abbreviation() {
+            lines.push(format!("TZNAME:{}", name));
+        }
+
+        lines.push(format!("END:{}", kind));
+        lines
+    };
+
+    // Starts before the week the recurrences start on
+    let start = Utc::now().date_naive() - Days::new(8);
+    let mut instant = start.and_time(Default::default()).and_utc();
+    let end = instant + Days::new(10 * 366);
+    let mut previous = offset_at(instant);
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", timezone)];
+
+    lines.extend(observance(&previous, &previous, instant));
+
+    while instant < end {
+        let next = instant + Days::new(1);
+        let offset = offset_at(next);
+
+        if offset.fix() != previous.fix() || offset.abbreviation() != previous.abbreviation() {
+            // Narrow the change down to the second
+            let (mut before, mut after) = (instant, next);
+
+            while after - before > TimeDelta::seconds(1) {
+                let middle = before + (after - before) / 2;
+
+                match offset_at(middle).fix() == previous.fix()
+                    && offset_at(middle).abbreviation() == previous.abbreviation()
+                {
+                    true => before = middle,
+                    false => after = middle,
+                }
+            }
+
+            lines.extend(observance(&previous, &offset, after));
+            previous = offset;
+        }
+
+        instant = next;
+    }
+
+    lines.push("END:VTIMEZONE".to_string());
+    lines
+}
+
+#[cfg(not(feature = "chrono"))]
+fn time_zone(_timezone: &Timezone) -> Vec<String> {
+    Vec::new()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// Splits lines longer than 75 octets, continuations starting with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut length = 0;
+
+    for character in line.chars() {
+        if length + character.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+
+        folded.push(character);
+        length += character.len_utf8();
+    }
+
+    folded
+}
+
+/// Renders the blocks as an iCalendar, with a weekly event per block repeated on
+/// the days of its day type. Times are local to `timezone`, usually
+/// [`crate::data::Home::date_time_zone`], which is described in the calendar with the
+/// `chrono` feature. Without it, times are floating and shown in the timezone of the reader.
+pub fn to_ical(zone_name: &str, blocks: &[ScheduleBlock], timezone: &Timezone) -> String {
+    let stamp = timestamp();
+    let time_zone_id = time_zone_id(timezone);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//tado//schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape(zone_name)),
+        format!("X-WR-TIMEZONE:{}", timezone),
+    ];
+
+    lines.extend(time_zone(timezone));
+
+    for (index, block) in blocks.iter().enumerate() {
+        let days = days(&block.day_type);
+        let first = *days.start();
+        let by_day: Vec<&str> = days.map(|day| WEEK[day].1).collect();
+
+        // 00:00 as end time is the midnight ending the day
+        let end_day = match block.end.as_str() {
+            "00:00" => first + 1,
+            _ => first,
+        };
+
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!(
+                "UID:{}-{}-{}@tado",
+                index,
+                block.day_type,
+                block.start.replace(':', "")
+            ),
+            format!("DTSTAMP:{}", stamp),
+            format!(
+                "DTSTART{}:{}T{}00",
+                time_zone_id,
+                week_day(timezone, first),
+                block.start.replace(':', "")
+            ),
+            format!(
+                "DTEND{}:{}T{}00",
+                time_zone_id,
+                week_day(timezone, end_day),
+                block.end.replace(':', "")
+            ),
+            format!("RRULE:FREQ=WEEKLY;BYDAY={}", by_day.join(",")),
+            format!(
+                "SUMMARY:{}",
+                escape(&format!("{} {}", zone_name, setting_label(&block.setting)))
+            ),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    // Lines of iCalendar end with CRLF
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+impl Client {
+    /// Blocks of the timetable followed by the zone, in the text format
+    pub async fn get_schedule_text(&self, zone: &ZoneId) -> Result<String, Error> {
+        Ok(to_text(&self.get_active_schedule_blocks(zone).await?))
+    }
+
+    /// Blocks of the timetable followed by the zone as an iCalendar, in the timezone of the home
+    pub async fn get_schedule_ical(&self, zone: &ZoneId) -> Result<String, Error> {
+        let home = self.get_home().await?;
+        let name = self.get_zone(zone).await?.name;
+        let blocks = self.get_active_schedule_blocks(zone).await?;
+
+        Ok(to_ical(&name, &blocks, &home.date_time_zone))
+    }
+
+    /// Parses the text and replaces the blocks of every day type it mentions. Air conditioning
+    /// zones are refused, as the text format can't hold their fan and swing settings.
+    pub async fn set_schedule_text(
+        &self,
+        zone: &ZoneId,
+        timetable: u32,
+        text: &str,
+    ) -> Result<Vec<ScheduleBlock>, Error> {
+        let capabilities = self.get_zone_capabilities(zone).await?;
+
+        if capabilities.r#type == ZoneType::AirConditioning {
+            return Err(Error::InvalidInput(format!(
+                "Schedules of {} zones cannot be edited as text",
+                ZoneType::AirConditioning
+            )));
+        }
+
+        let blocks = parse_text(text, &capabilities.r#type)?;
+
+        for block in &blocks {
+            capabilities.validate(&block.setting).map_err(|error| {
+                Error::InvalidInput(format!(
+                    "{} {}-{}: {}",
+                    day_label(&block.day_type),
+                    block.start,
+                    block.end,
+                    error
+                ))
+            })?;
+        }
+
+        let mut applied = Vec::new();
+        let mut day_types: Vec<&DayType> = Vec::new();
+
+        for block in &blocks {
+            if !day_types.contains(&&block.day_type) {
+                day_types.push(&block.day_type);
+            }
+        }
+
+        for day_type in day_types {
+            let day_blocks: Vec<ScheduleBlock> = blocks
+                .iter()
+                .filter(|block| block.day_type == *day_type)
+                .cloned()
+                .collect();
+
+            applied.extend(
+                self.set_schedule_blocks(zone, timetable, day_type, &day_blocks)
+                    .await?,
+            );
+        }
+
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "Mon-Fri 06:30-08:00 21°C
+Mon-Fri 08:00-00:00 off
+Sat 00:00-00:00 19.5°C geolocation-override
+Mon-Sun 22:00-23:00 on";
+
+    fn json(blocks: &[ScheduleBlock]) -> serde_json::Value {
+        serde_json::to_value(blocks).unwrap()
+    }
+
+    fn unfold(ical: &str) -> String {
+        ical.replace("\r\n ", "")
+    }
+
+    #[test]
+    fn parses_text() {
+        let blocks = parse_text(
+            "# Weekdays\n\n  mon-fri 06:30-08:00 21C\nSat 00:00-00:00 19.5°C GEOLOCATION-OVERRIDE\nSun 07:00-09:00 off",
+            &ZoneType::Heating,
+        )
+        .unwrap();
+
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].day_type, DayType::MondayToFriday);
+        assert_eq!(
+            (&blocks[0].start[..], &blocks[0].end[..]),
+            ("06:30", "08:00")
+        );
+        assert_eq!(blocks[0].setting.power, Power::On);
+        assert_eq!(
+            blocks[0].setting.temperature.as_ref().unwrap().celsius,
+            21.0
+        );
+        assert!(!blocks[0].geolocation_override);
+        assert_eq!(blocks[1].day_type, DayType::Saturday);
+        assert!(blocks[1].geolocation_override);
+        assert_eq!(blocks[2].setting.power, Power::Off);
+        assert!(blocks[2].setting.temperature.is_none());
+    }
+
+    #[test]
+    fn round_trips_text() {
+        let blocks = parse_text(TEXT, &ZoneType::Heating).unwrap();
+
+        assert_eq!(to_text(&blocks), TEXT);
+        assert_eq!(
+            json(&parse_text(&to_text(&blocks), &ZoneType::Heating).unwrap()),
+            json(&blocks)
+        );
+    }
+
+    #[test]
+    fn round_trips_air_conditioning_modes() {
+        let text = "Mon-Fri 08:00-18:00 22°C cool\nSun 08:00-18:00 off";
+        let blocks = parse_text(text, &ZoneType::AirConditioning).unwrap();
+
+        assert_eq!(blocks[0].setting.mode, Some(AcMode::Cool));
+        assert_eq!(to_text(&blocks), text);
+    }
+
+    #[test]
+    fn rejects_modes_outside_of_air_conditioning() {
+        assert!(parse_text("Mon 06:00-08:00 21°C cool", &ZoneType::Heating).is_err());
+        assert!(parse_text("Mon 06:00-08:00 50°C heat", &ZoneType::HotWater).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for line in [
+            "Mon 06:00-08:00",
+            "Weekend 06:00-08:00 21°C",
+            "Mon 08:00-06:00 21°C",
+            "Mon 06:00-24:00 21°C",
+            "Mon 6:00-08:00 21°C",
+            "Mon 06:00 21°C",
+            "Mon 06:00-08:00 warm",
+            "Mon 06:00-08:00 40°C",
+            "Mon 06:00-08:00 21°C eco",
+        ] {
+            assert!(
+                parse_text(line, &ZoneType::Heating).is_err(),
+                "{} was accepted",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        let error = parse_text(
+            "Mon 06:00-08:00 21°C\n\nMon 08:00-07:00 21°C",
+            &ZoneType::Heating,
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("Line 3"));
+    }
+
+    #[test]
+    fn renders_ical() {
+        let blocks = parse_text(TEXT, &ZoneType::Heating).unwrap();
+        let timezone: Timezone = "Europe/Rome".parse().unwrap();
+        let name = "Living room, first floor with a very long name that needs folding";
+        let ical = to_ical(name, &blocks, &timezone);
+
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+        assert!(ical
+            .split("\r\n")
+            .all(|line| line.len() <= 75 && !line.contains('\n')));
+
+        let ical = unfold(&ical);
+
+        assert_eq!(ical.matches("BEGIN:VEVENT").count(), 4);
+        assert!(ical.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR\r\n"));
+        assert!(ical.contains("RRULE:FREQ=WEEKLY;BYDAY=SA\r\n"));
+        assert!(ical.contains(
+            "SUMMARY:Living room\\, first floor with a very long name that needs folding 21°C\r\n"
+        ));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn describes_the_timezone() {
+        let blocks = parse_text(TEXT, &ZoneType::Heating).unwrap();
+        let ical = unfold(&to_ical("Living", &blocks, &"Europe/Rome".parse().unwrap()));
+
+        assert_eq!(ical.matches("BEGIN:VTIMEZONE").count(), 1);
+        assert!(ical.contains("TZID:Europe/Rome\r\n"));
+        assert!(ical.contains("TZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\n"));
+        assert!(ical.contains("TZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\n"));
+        assert!(ical.contains("BEGIN:DAYLIGHT"));
+        assert!(ical.contains("DTSTART;TZID=Europe/Rome:"));
+
+        // Timezones without daylight saving time keep a single observance
+        let ical = to_ical("Living", &blocks, &"Asia/Tokyo".parse().unwrap());
+
+        assert_eq!(ical.matches("BEGIN:STANDARD").count(), 1);
+        assert!(!ical.contains("BEGIN:DAYLIGHT"));
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    #[test]
+    fn uses_floating_times_without_chrono() {
+        let blocks = parse_text(TEXT, &ZoneType::Heating).unwrap();
+        let ical = to_ical("Living", &blocks, &"Europe/Rome".to_string());
+
+        assert!(!ical.contains("TZID"));
+        assert!(ical.contains("DTSTART:20240101T063000\r\n"));
+        assert!(ical.contains("DTEND:20240102T000000\r\n"));
+    }
+
+    #[test]
+    fn folds_without_splitting_characters() {
+        let line = format!("SUMMARY:{}", "°".repeat(60));
+        let folded = fold(&line);
+
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,216 @@
+//! Full captures of a home, kept as backups before firmware updates or reinstallations
+//! and replayed onto the same or another home through [`crate::reconcile`].
+
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        new_type, AwayConfiguration, DefaultOverlay, Device, DeviceId, Home, ScheduleBlock,
+        Temperature, Timetable, Zone, ZoneFeatures,
+    },
+    reconcile::{DeviceDescription, HomeDescription, Plan, ScheduleDescription, ZoneDescription},
+    Client, Error,
+};
+
+new_type![ZoneSnapshot {
+    zone: Zone,
+    features: ZoneFeatures,
+    away_configuration: AwayConfiguration,
+    default_overlay: DefaultOverlay,
+    timetable: Timetable,
+    /// Blocks of the active timetable
+    blocks: Vec<ScheduleBlock>,
+}];
+
+new_type![DeviceSnapshot {
+    device: Device,
+    /// Only read for devices measuring the temperature
+    temperature_offset: Option<Temperature>,
+}];
+
+new_type![Snapshot {
+    home: Home,
+    zones: Vec<ZoneSnapshot>,
+    devices: Vec<DeviceSnapshot>,
+}];
+
+impl Snapshot {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, Error> {
+        serde_json::from_str(source).map_err(|error| Error::InvalidInput(error.to_string()))
+    }
+
+    /// Writable parts of the snapshot, with zones matched by name so it applies to other homes
+    pub fn description(&self) -> HomeDescription {
+        HomeDescription {
+            away_radius_in_meters: Some(self.home.away_radius_in_meters),
+            temperature_unit: Some(self.home.temperature_unit.clone()),
+            christmas_mode: Some(self.home.christmas_mode_enabled),
+            zones: self
+                .zones
+                .iter()
+                .map(|zone| ZoneDescription {
+                    id: None,
+                    name: zone.zone.name.clone(),
+                    features: zone.features.clone(),
+                    away_configuration: Some(zone.away_configuration.clone()),
+                    default_overlay: Some(zone.default_overlay.clone()),
+                    schedule: Some(ScheduleDescription {
+                        timetable: zone.timetable.id,
+                        blocks: zone.blocks.clone(),
+                    }),
+                })
+                .collect(),
+            devices: self
+                .devices
+                .iter()
+                .map(|device| DeviceDescription {
+                    serial_no: device.device.serial_no.clone(),
+                    child_lock: device.device.child_lock_enabled,
+                    temperature_offset: device
+                        .temperature_offset
+                        .as_ref()
+                        .map(|offset| offset.celsius),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Restore {
+    pub plan: Plan,
+    /// Zones of the snapshot without a zone of the same name in the home
+    pub unmatched_zones: Vec<String>,
+    /// Devices of the snapshot that are not part of the home
+    pub unmatched_devices: Vec<DeviceId>,
+    /// Features enabled in the snapshot, by zone name, that the matching zone doesn't support
+    pub unsupported_features: Vec<(String, String)>,
+}
+
+impl Display for Restore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.plan)?;
+
+        for zone in &self.unmatched_zones {
+            writeln!(f, "! zone \"{}\" not found, skipped", zone)?;
+        }
+
+        for device in &self.unmatched_devices {
+            writeln!(f, "! device {} not found, skipped", device)?;
+        }
+
+        for (zone, feature) in &self.unsupported_features {
+            writeln!(
+                f,
+                "! zone \"{}\" does not support {}, skipped",
+                zone, feature
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Captures the home, its zones and devices with their settings
+    pub async fn get_snapshot(&self) -> Result<Snapshot, Error> {
+        let home = self.get_home().await?;
+        let mut zones = Vec::new();
+
+        for zone in self.get_zones().await? {
+            let timetable = self.get_schedule(&zone.id).await?;
+
+            zones.push(ZoneSnapshot {
+                features: self.get_zone_features(&zone.id).await?,
+                away_configuration: self.get_away_configuration(&zone.id).await?,
+                default_overlay: self.get_default_overlay(&zone.id).await?,
+                blocks: self.get_schedule_blocks(&zone.id, timetable.id).await?,
+                timetable,
+                zone,
+            });
+        }
+
+        let mut devices = Vec::new();
+
+        for device in self.get_devices().await? {
+            let temperature_offset = match device.can_measure_temperature() {
+                true => Some(
+                    self.get_temperature_offset(device.serial_no.clone())
+                        .await?,
+                ),
+                false => None,
+            };
+
+            devices.push(DeviceSnapshot {
+                device,
+                temperature_offset,
+            });
+        }
+
+        Ok(Snapshot {
+            home,
+            zones,
+            devices,
+        })
+    }
+
+    /// Replays the snapshot onto the home, skipping the zones, devices and features it doesn't have.
+    /// In dry run, only the plan of the differences is returned.
+    pub async fn restore(&self, snapshot: &Snapshot, dry_run: bool) -> Result<Restore, Error> {
+        let zones = self.get_zones().await?;
+        let devices = self.get_devices().await?;
+        let mut description = snapshot.description();
+        let mut restore = Restore::default();
+
+        description.zones.retain_mut(|zone| {
+            let Some(target) = zones.iter().find(|candidate| candidate.name == zone.name) else {
+                restore.unmatched_zones.push(zone.name.clone());
+                return false;
+            };
+
+            // Features the zone can't have are left out instead of failing the whole restore
+            let features = &mut zone.features;
+
+            if !target.dazzle_mode.supported && features.dazzle.take() == Some(true) {
+                restore
+                    .unsupported_features
+                    .push((zone.name.clone(), "dazzle mode".to_string()));
+            }
+
+            if !target.open_window_detection.supported {
+                features.open_window_detection_timeout_in_seconds = None;
+
+                if features.open_window_detection.take() == Some(true) {
+                    restore
+                        .unsupported_features
+                        .push((zone.name.clone(), "open window detection".to_string()));
+                }
+            }
+
+            true
+        });
+
+        description.devices.retain(|device| {
+            let found = devices
+                .iter()
+                .any(|candidate| candidate.serial_no == device.serial_no);
+
+            if !found {
+                restore.unmatched_devices.push(device.serial_no.clone());
+            }
+
+            found
+        });
+
+        restore.plan = self.reconcile(&description, dry_run).await?;
+
+        Ok(restore)
+    }
+}
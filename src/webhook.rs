@@ -0,0 +1,496 @@
+//! Forwards alerts and state changes observed by polling the [`Client`] to HTTP webhooks,
+//! as signed JSON payloads.
+
+use std::{collections::HashMap, time::Duration};
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sha2::Sha256;
+
+use crate::{
+    alert::{AlertEvent, AlertSink},
+    data::{StatePresence, ZoneId, ZoneSetting},
+    Client, Error,
+};
+
+/// Header carrying the hex encoded HMAC-SHA256 of the body, prefixed by `sha256=`
+pub static SIGNATURE_HEADER: &str = "X-Tado-Signature";
+
+/// Longest delay between two attempts of a delivery
+pub const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StateChange {
+    #[serde(rename_all = "camelCase")]
+    PresenceChanged {
+        presence: StatePresence,
+        presence_locked: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    ZoneChanged {
+        zone: ZoneId,
+        setting: Option<ZoneSetting>,
+        manual: bool,
+        open_window_detected: bool,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WebhookEvent {
+    Alert { event: AlertEvent },
+    StateChange { change: StateChange },
+}
+
+/// Polls the home and zone states, reporting what changed since the previous poll
+#[derive(Default)]
+pub struct StateWatcher {
+    presence: Option<(StatePresence, bool)>,
+    zones: HashMap<ZoneId, JsonValue>,
+}
+
+impl StateWatcher {
+    pub async fn poll(&mut self, client: &Client) -> Result<Vec<StateChange>, Error> {
+        let mut changes = Vec::new();
+        let home = client.get_home_state().await?;
+        let presence = (home.presence, home.presence_locked);
+
+        if self.presence.as_ref() != Some(&presence) {
+            changes.push(StateChange::PresenceChanged {
+                presence: presence.0.clone(),
+                presence_locked: presence.1,
+            });
+            self.presence = Some(presence);
+        }
+
+        for zone in client.get_zones().await? {
+            let state = client.get_state(&zone.id).await?;
+            let change = StateChange::ZoneChanged {
+                zone: zone.id,
+                setting: state.setting,
+                manual: state.overlay.is_some(),
+                open_window_detected: state.open_window_detected.unwrap_or(false),
+            };
+
+            // Temperatures are floats, so compare the serialized form
+            let serialized = serde_json::to_value(&change).unwrap_or_default();
+
+            if self.zones.get(&zone.id) != Some(&serialized) {
+                self.zones.insert(zone.id, serialized);
+                changes.push(change);
+            }
+        }
+
+        Ok(changes)
+    }
+
+    // Forgets a change that couldn't be delivered, so the next poll reports it again
+    fn forget(&mut self, change: &StateChange) {
+        match change {
+            StateChange::PresenceChanged { .. } => self.presence = None,
+            StateChange::ZoneChanged { zone, .. } => {
+                self.zones.remove(zone);
+            }
+        }
+    }
+}
+
+pub struct WebhookConfiguration {
+    pub urls: Vec<String>,
+    /// Key used to sign the payloads, unsigned when none
+    pub secret: Option<String>,
+    /// Attempts made after the first failure of each delivery
+    pub retries: u32,
+    /// Delay before the first retry, doubled at each following one up to [`MAX_BACKOFF`]
+    pub backoff: Duration,
+    /// Time limit of each attempt
+    pub timeout: Duration,
+}
+
+impl Default for WebhookConfiguration {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            secret: None,
+            retries: 3,
+            backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+pub struct WebhookSink {
+    inner: reqwest::Client,
+    configuration: WebhookConfiguration,
+}
+
+impl WebhookSink {
+    pub fn new(configuration: WebhookConfiguration) -> Self {
+        let builder = reqwest::Client::builder().timeout(configuration.timeout);
+
+        Self {
+            inner: builder.build().unwrap(),
+            configuration,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt)
+            .and_then(|factor| self.configuration.backoff.checked_mul(factor))
+            .map_or(MAX_BACKOFF, |delay| delay.min(MAX_BACKOFF))
+    }
+
+    /// Posts the event to every configured URL, even when some of them fail
+    pub async fn deliver(&self, event: &WebhookEvent) -> Result<(), Error> {
+        let failures = self.post(event).await;
+
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Delivery(failures)),
+        }
+    }
+
+    async fn post(&self, event: &WebhookEvent) -> Vec<(String, reqwest::Error)> {
+        // Safe to unwrap as the events only contain serializable data
+        let body = serde_json::to_vec(event).unwrap();
+        let signature = self
+            .configuration
+            .secret
+            .as_ref()
+            .map(|secret| sign(secret.as_bytes(), &body));
+
+        let mut failures = Vec::new();
+
+        for url in &self.configuration.urls {
+            let mut attempt = 0;
+
+            loop {
+                let mut request = self
+                    .inner
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.clone());
+
+                if let Some(signature) = &signature {
+                    request = request.header(SIGNATURE_HEADER, signature);
+                }
+
+                let result = match request.send().await {
+                    Ok(response) => response.error_for_status().map(|_| ()),
+                    Err(error) => Err(error),
+                };
+
+                match result {
+                    Ok(()) => break,
+                    Err(error) if attempt < self.configuration.retries && is_transient(&error) => {
+                        tokio::time::sleep(self.backoff(attempt)).await;
+                        attempt += 1;
+                    }
+                    Err(error) => {
+                        failures.push((url.clone(), error));
+                        break;
+                    }
+                }
+            }
+        }
+
+        failures
+    }
+
+    /// Polls state changes once and delivers them. Changes that are not delivered
+    /// to every URL are reported again on the next poll.
+    pub async fn forward_changes(
+        &self,
+        watcher: &mut StateWatcher,
+        client: &Client,
+    ) -> Result<Vec<StateChange>, Error> {
+        let changes = watcher.poll(client).await?;
+        let mut failures = Vec::new();
+
+        for change in &changes {
+            let failed = self
+                .post(&WebhookEvent::StateChange {
+                    change: change.clone(),
+                })
+                .await;
+
+            if !failed.is_empty() {
+                watcher.forget(change);
+                failures.extend(failed);
+            }
+        }
+
+        match failures.is_empty() {
+            true => Ok(changes),
+            false => Err(Error::Delivery(failures)),
+        }
+    }
+}
+
+impl AlertSink for WebhookSink {
+    async fn send(&self, event: &AlertEvent) -> Result<(), Error> {
+        self.deliver(&WebhookEvent::Alert {
+            event: event.clone(),
+        })
+        .await
+    }
+}
+
+// Failures worth another attempt: unreachable endpoints, server errors and rate limits
+fn is_transient(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        None => error.is_connect() || error.is_timeout(),
+    }
+}
+
+/// Signature sent in the [`SIGNATURE_HEADER`] for the given body
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    // Safe to unwrap as HMAC accepts keys of any size
+    let mut mac = HmacSha256::new_from_slice(secret).unwrap();
+    mac.update(body);
+
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    format!("sha256={}", hex)
+}
+
+/// Checks, in constant time, a signature received by a webhook endpoint
+pub fn verify(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let Some(hex) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+
+    let bytes: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            hex.get(index..index + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect();
+
+    let Some(bytes) = bytes else {
+        return false;
+    };
+
+    // Safe to unwrap as HMAC accepts keys of any size
+    let mut mac = HmacSha256::new_from_slice(secret).unwrap();
+    mac.update(body);
+    mac.verify_slice(&bytes).is_ok()
+}
+
+#[cfg(all(test, feature = "webhook"))]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    };
+
+    use super::*;
+    use crate::alert::Alert;
+
+    struct Received {
+        signature: Option<String>,
+        body: String,
+    }
+
+    /// Local endpoint answering each request with the next status, 204 once they run out
+    async fn endpoint(statuses: Vec<u16>) -> (String, UnboundedReceiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        let (sender, receiver) = unbounded_channel();
+
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+
+                // Read until the whole body announced by the headers arrived
+                let (head, body) = loop {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+
+                    let text = String::from_utf8_lossy(&request).to_string();
+
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|value| value.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+
+                        if body.len() >= length {
+                            break (head.to_string(), body.to_string());
+                        }
+                    }
+                };
+
+                let status = statuses.next().unwrap_or(204);
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+
+                let signature = head.lines().find_map(|line| {
+                    line.split_once(": ")
+                        .filter(|(name, _)| name.eq_ignore_ascii_case(SIGNATURE_HEADER))
+                        .map(|(_, value)| value.to_string())
+                });
+
+                let _ = sender.send(Received { signature, body });
+            }
+        });
+
+        (url, receiver)
+    }
+
+    fn sink(urls: Vec<String>, retries: u32) -> WebhookSink {
+        WebhookSink::new(WebhookConfiguration {
+            urls,
+            secret: Some("secret".to_string()),
+            retries,
+            backoff: Duration::from_millis(1),
+            ..Default::default()
+        })
+    }
+
+    fn battery_low() -> AlertEvent {
+        AlertEvent::Raised {
+            alert: Alert::BatteryLow {
+                device: "VA1234567890".to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn signs_the_payload() {
+        let (url, mut received) = endpoint(vec![]).await;
+
+        sink(vec![url], 0).send(&battery_low()).await.unwrap();
+
+        let request = received.recv().await.unwrap();
+        let signature = request.signature.unwrap();
+
+        assert!(request.body.contains("BATTERY_LOW"));
+        assert!(verify(b"secret", request.body.as_bytes(), &signature));
+        assert!(!verify(b"other", request.body.as_bytes(), &signature));
+        assert!(!verify(b"secret", b"{}", &signature));
+    }
+
+    #[tokio::test]
+    async fn retries_after_server_errors() {
+        let (url, mut received) = endpoint(vec![500, 503]).await;
+
+        sink(vec![url], 3).send(&battery_low()).await.unwrap();
+
+        for _ in 0..3 {
+            received.recv().await.unwrap();
+        }
+
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn only_retries_transient_errors() {
+        let (url, mut received) = endpoint(vec![400, 429]).await;
+
+        assert!(sink(vec![url.clone()], 3)
+            .send(&battery_low())
+            .await
+            .is_err());
+        received.recv().await.unwrap();
+        assert!(received.try_recv().is_err());
+
+        sink(vec![url], 3).send(&battery_low()).await.unwrap();
+
+        for _ in 0..2 {
+            received.recv().await.unwrap();
+        }
+
+        assert!(received.try_recv().is_err());
+    }
+
+    #[test]
+    fn undelivered_changes_are_reported_again() {
+        let mut watcher = StateWatcher::default();
+        let change = StateChange::ZoneChanged {
+            zone: 1,
+            setting: None,
+            manual: false,
+            open_window_detected: false,
+        };
+
+        watcher
+            .zones
+            .insert(1, serde_json::to_value(&change).unwrap());
+        watcher.presence = Some((StatePresence::Home, false));
+
+        watcher.forget(&change);
+        assert!(watcher.zones.is_empty());
+        assert!(watcher.presence.is_some());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_retries() {
+        let (url, mut received) = endpoint(vec![500; 10]).await;
+
+        let error = sink(vec![url.clone()], 2)
+            .send(&battery_low())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(&error, Error::Delivery(failures) if failures[0].0 == url));
+
+        for _ in 0..3 {
+            received.recv().await.unwrap();
+        }
+
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn failing_urls_do_not_block_the_others() {
+        let (failing, _) = endpoint(vec![500; 10]).await;
+        let (working, mut received) = endpoint(vec![]).await;
+
+        let error = sink(vec![failing.clone(), working], 1)
+            .send(&battery_low())
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(&error, Error::Delivery(failures) if failures.len() == 1 && failures[0].0 == failing)
+        );
+        assert!(received.recv().await.is_some());
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let mut sink = sink(vec![], 100);
+
+        assert_eq!(sink.backoff(2), Duration::from_millis(4));
+        assert_eq!(sink.backoff(40), MAX_BACKOFF);
+
+        sink.configuration.backoff = Duration::MAX;
+        assert_eq!(sink.backoff(1), MAX_BACKOFF);
+    }
+}
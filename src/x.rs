@@ -0,0 +1,211 @@
+//! tado X (Matter/Thread line) homes, served by the hops API with rooms instead of zones.
+//! It shares the authentication and session of the [`Client`] it is created from,
+//! use [`crate::data::Home::is_tado_x`] to pick the right API for a home.
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use strum::Display;
+
+use crate::{
+    cnf::HOPS_URL,
+    data::{
+        new_enum, new_type, Date, DayType, DeviceBatteryState, DeviceId, Power, Temperature,
+        ZoneType,
+    },
+    Client, Error,
+};
+
+pub type RoomId = u32;
+
+new_type![TemperatureValue { value: f32 }];
+
+new_type![Percentage { percentage: f32 }];
+
+new_enum![ConnectionState {
+    Connected,
+    Disconnected,
+}];
+
+new_type![Connection {
+    state: ConnectionState
+}];
+
+new_type![RoomSensorDataPoints {
+    inside_temperature: Option<TemperatureValue>,
+    humidity: Option<Percentage>,
+}];
+
+new_type![RoomSetting {
+    power: Power,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<TemperatureValue>,
+}];
+
+impl RoomSetting {
+    pub fn validate(&self) -> Result<(), Error> {
+        match (&self.power, &self.temperature) {
+            (Power::On, Some(temperature)) => ZoneType::Heating
+                .validate_temperature(&Temperature::from_celsius(temperature.value)),
+            _ => Ok(()),
+        }
+    }
+}
+
+new_enum![
+    #[serde(tag = "type", rename_all_fields = "camelCase")]
+    RoomTermination {
+        Manual,
+        NextTimeBlock,
+        Timer { duration_in_seconds: u32 }
+    }
+];
+
+new_type![RoomManualControlTermination {
+    r#type: String,
+    remaining_time_in_seconds: Option<u32>,
+    projected_expiry: Option<Date>,
+}];
+
+new_type![RoomState {
+    id: RoomId,
+    name: String,
+    sensor_data_points: RoomSensorDataPoints,
+    setting: RoomSetting,
+    manual_control_termination: Option<RoomManualControlTermination>,
+    heating_power: Option<Percentage>,
+    connection: Option<Connection>,
+}];
+
+new_type![RoomDevice {
+    serial_number: DeviceId,
+    r#type: String,
+    firmware_version: String,
+    connection: Connection,
+    battery_state: Option<DeviceBatteryState>,
+    temperature_as_measured: Option<f32>,
+    temperature_offset: Option<f32>,
+    child_lock_enabled: Option<bool>,
+}];
+
+new_type![RoomWithDevices {
+    room_id: RoomId,
+    room_name: String,
+    devices: Vec<RoomDevice>,
+}];
+
+new_type![RoomsAndDevices {
+    rooms: Vec<RoomWithDevices>,
+    other_devices: Vec<RoomDevice>,
+}];
+
+new_type![RoomScheduleBlock {
+    day_type: DayType,
+    start: String,
+    end: String,
+    setting: RoomSetting,
+}];
+
+new_type![RoomSchedule {
+    schedule: Vec<RoomScheduleBlock>,
+}];
+
+pub struct TadoX<'a> {
+    client: &'a Client,
+}
+
+impl Client {
+    /// Access to the API of tado X homes
+    pub fn tado_x(&self) -> TadoX<'_> {
+        TadoX { client: self }
+    }
+}
+
+impl TadoX<'_> {
+    async fn url(&self, path: &str) -> Result<String, Error> {
+        Ok(format!(
+            "{}homes/{}/{}",
+            HOPS_URL,
+            self.client.home_id().await?,
+            path
+        ))
+    }
+
+    pub async fn get_rooms(&self) -> Result<Vec<RoomState>, Error> {
+        let url = self.url("rooms").await?;
+
+        response!(self.client, Method::GET, url)
+    }
+
+    pub async fn get_room(&self, room: &RoomId) -> Result<RoomState, Error> {
+        let url = self.url(&format!("rooms/{}", room)).await?;
+
+        response!(self.client, Method::GET, url)
+    }
+
+    pub async fn get_rooms_and_devices(&self) -> Result<RoomsAndDevices, Error> {
+        let url = self.url("roomsAndDevices").await?;
+
+        response!(self.client, Method::GET, url)
+    }
+
+    /// Devices of the home, whether they are assigned to a room or not
+    pub async fn get_devices(&self) -> Result<Vec<RoomDevice>, Error> {
+        let rooms = self.get_rooms_and_devices().await?;
+
+        Ok(rooms
+            .rooms
+            .into_iter()
+            .flat_map(|room| room.devices)
+            .chain(rooms.other_devices)
+            .collect())
+    }
+
+    pub async fn get_schedule(&self, room: &RoomId) -> Result<RoomSchedule, Error> {
+        let url = self.url(&format!("rooms/{}/schedule", room)).await?;
+
+        response!(self.client, Method::GET, url)
+    }
+
+    pub async fn set_manual_control(
+        &self,
+        room: &RoomId,
+        setting: &RoomSetting,
+        termination: &RoomTermination,
+    ) -> Result<(), Error> {
+        setting.validate()?;
+
+        let url = self.url(&format!("rooms/{}/manualControl", room)).await?;
+
+        response!(@empty self.client, Method::POST, url, {
+            "setting": setting,
+            "termination": termination,
+        })
+    }
+
+    /// Ends manual control of the room, going back to its schedule
+    pub async fn resume_schedule(&self, room: &RoomId) -> Result<(), Error> {
+        let url = self.url(&format!("rooms/{}/manualControl", room)).await?;
+
+        response!(@empty self.client, Method::DELETE, url, null)
+    }
+
+    /// Boosts heating in every room of the home
+    pub async fn boost(&self) -> Result<(), Error> {
+        let url = self.url("quickActions/boost").await?;
+
+        response!(@empty self.client, Method::POST, url, null)
+    }
+
+    pub async fn resume_all_schedules(&self) -> Result<(), Error> {
+        let url = self.url("quickActions/resumeSchedule").await?;
+
+        response!(@empty self.client, Method::POST, url, null)
+    }
+
+    pub async fn turn_all_off(&self) -> Result<(), Error> {
+        let url = self.url("quickActions/allOff").await?;
+
+        response!(@empty self.client, Method::POST, url, null)
+    }
+}